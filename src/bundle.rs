@@ -0,0 +1,353 @@
+use anyhow::{anyhow, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use crate::manifest::{Manifest, MANIFEST_FILE};
+use crate::origin::ORIGIN_FILE;
+use crate::package::CONTAINER_FILE;
+
+// An SDK bundle is a `.tar.gz` or `.zip` of an SDK directory: the runtime,
+// its standard library and `sdk.toml`. `sdk export` stores the files under
+// `<language>/<version>/`, which tells `sdk import` where they belong.
+#[derive(Clone, Copy)]
+pub enum Format {
+    TarGz,
+    Zip,
+}
+
+// Bundles are recognized by their content, since downloads keep no name.
+pub fn format(path: &Path) -> Result<Option<Format>> {
+    let mut magic = [0; 4];
+    let read = File::open(path)?.read(&mut magic)?;
+    Ok(match &magic[..read] {
+        [0x1f, 0x8b, ..] => Some(Format::TarGz),
+        [b'P', b'K', 3, 4] => Some(Format::Zip),
+        _ => None,
+    })
+}
+
+fn invalid(path: &Path, e: impl Display) -> anyhow::Error {
+    anyhow!("Invalid bundle '{}': {}", path.display(), e)
+}
+
+// Every entry of the bundle, with whether it is a directory.
+fn entries(path: &Path, format: Format) -> Result<Vec<(PathBuf, bool)>> {
+    let mut entries = Vec::new();
+    match format {
+        Format::TarGz => {
+            let mut archive = tar::Archive::new(GzDecoder::new(File::open(path)?));
+            for entry in archive.entries().map_err(|e| invalid(path, e))? {
+                let entry = entry.map_err(|e| invalid(path, e))?;
+                let name = entry.path().map_err(|e| invalid(path, e))?.into_owned();
+                entries.push((name, entry.header().entry_type().is_dir()));
+            }
+        }
+        Format::Zip => {
+            let mut archive = zip::ZipArchive::new(File::open(path)?).map_err(|e| invalid(path, e))?;
+            for i in 0..archive.len() {
+                let file = archive.by_index(i).map_err(|e| invalid(path, e))?;
+                entries.push((PathBuf::from(file.name()), file.is_dir()));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+// Returns the language and version of a bundle written by `sdk export`, or
+// None when its files are not all under one `<language>/<version>/`.
+pub fn identify(path: &Path) -> Result<Option<(String, String)>> {
+    let Some(format) = format(path)? else {
+        return Ok(None);
+    };
+    let mut found: Option<(String, String)> = None;
+    for (name, is_dir) in entries(path, format)? {
+        let parts: Option<Vec<&str>> = name
+            .components()
+            .filter(|c| !matches!(c, Component::CurDir))
+            .map(|c| match c {
+                Component::Normal(part) => part.to_str(),
+                _ => None,
+            })
+            .collect();
+        let prefix = match parts.as_deref() {
+            Some([language, version, _, ..]) if !language.starts_with('.') && !version.starts_with('.') => {
+                (language.to_string(), version.to_string())
+            }
+            Some(_) if is_dir => continue,
+            _ => return Ok(None),
+        };
+        match &found {
+            Some(found) if *found != prefix => return Ok(None),
+            Some(_) => {}
+            None => found = Some(prefix),
+        }
+    }
+    Ok(found)
+}
+
+// Extracts a bundle into `dest`, dropping the `<language>/<version>/`
+// directories of an exported bundle.
+pub fn unpack(path: &Path, format: Format, dest: &Path) -> Result<()> {
+    match format {
+        Format::TarGz => {
+            let mut archive = tar::Archive::new(GzDecoder::new(File::open(path)?));
+            archive.unpack(dest).map_err(|e| invalid(path, e))?;
+        }
+        Format::Zip => {
+            let mut archive = zip::ZipArchive::new(File::open(path)?).map_err(|e| invalid(path, e))?;
+            for i in 0..archive.len() {
+                let mut file = archive.by_index(i).map_err(|e| invalid(path, e))?;
+                let name = file
+                    .enclosed_name()
+                    .map(Path::to_path_buf)
+                    .ok_or_else(|| invalid(path, format!("unsafe path '{}'", file.name())))?;
+                let target = dest.join(name);
+                if file.is_dir() {
+                    fs::create_dir_all(&target)?;
+                } else {
+                    if let Some(parent) = target.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    io::copy(&mut file, &mut File::create(&target)?)?;
+                }
+            }
+        }
+    }
+    if let Some((language, version)) = identify(path)? {
+        hoist(dest, &language, &version)?;
+    }
+    if !dest.join(MANIFEST_FILE).is_file() && !dest.join("runtime.wasm").is_file() {
+        return Err(invalid(path, format!("it contains neither runtime.wasm nor {} at its root", MANIFEST_FILE)));
+    }
+    Ok(())
+}
+
+// Moves the SDK out of `<language>/<version>/`. The language directory is
+// renamed first, since the SDK may contain an entry of the same name.
+fn hoist(dest: &Path, language: &str, version: &str) -> Result<()> {
+    let wrapper = dest.join(".bundle-root");
+    fs::rename(dest.join(language), &wrapper)?;
+    for entry in fs::read_dir(wrapper.join(version))? {
+        let entry = entry?;
+        fs::rename(entry.path(), dest.join(entry.file_name()))?;
+    }
+    fs::remove_dir_all(&wrapper)?;
+    Ok(())
+}
+
+// Writes the SDK at `sdk_path` to a `.tar.gz` bundle. Compiled caches and
+// the origin record are specific to this machine and are left out, as is a
+// Wasmer package that has already been unpacked.
+pub fn export(sdk_path: &Path, language: &str, version: &str, output: &Path) -> Result<()> {
+    let unpacked = Manifest::load(sdk_path)?.unwrap_or_default().wasm_path(sdk_path).is_file();
+    let skip = |name: &str| {
+        name == ORIGIN_FILE || is_cache(name) || name.starts_with('.') || (unpacked && name == CONTAINER_FILE)
+    };
+    let dir = output.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let file = tempfile::NamedTempFile::new_in(dir)?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    append(&mut builder, sdk_path, &Path::new(language).join(version), &skip)?;
+    let file = builder.into_inner()?.finish()?;
+    file.persist(output)?;
+    Ok(())
+}
+
+// Compiled modules and runtime digests cached next to a runtime.
+fn is_cache(name: &str) -> bool {
+    name.ends_with(".cwasm") || (name.starts_with('.') && name.ends_with(".digest"))
+}
+
+fn append<W: Write>(
+    builder: &mut tar::Builder<W>,
+    dir: &Path,
+    name: &Path,
+    skip: &dyn Fn(&str) -> bool,
+) -> Result<()> {
+    builder.append_dir(name, dir)?;
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let file_name = entry.file_name();
+        if skip(&file_name.to_string_lossy()) {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            append(builder, &path, &name.join(&file_name), &is_cache)?;
+        } else {
+            builder.append_path_with_name(&path, name.join(&file_name))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tar_gz(dir: &Path, entries: &[(&str, &str)]) -> PathBuf {
+        let path = dir.join("bundle.tar.gz");
+        let mut builder = tar::Builder::new(GzEncoder::new(File::create(&path).unwrap(), Compression::default()));
+        for (name, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            // Written as raw bytes, since `set_path` refuses `..`.
+            header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, content.as_bytes()).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+        path
+    }
+
+    fn zip(dir: &Path, entries: &[(&str, &str)]) -> PathBuf {
+        let path = dir.join("bundle.zip");
+        let mut writer = zip::ZipWriter::new(File::create(&path).unwrap());
+        let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        for (name, content) in entries {
+            if let Some(name) = name.strip_suffix('/') {
+                writer.add_directory(name, options).unwrap();
+            } else {
+                writer.start_file(*name, options).unwrap();
+                writer.write_all(content.as_bytes()).unwrap();
+            }
+        }
+        writer.finish().unwrap();
+        path
+    }
+
+    fn identify_entries(entries: &[(&str, &str)]) -> Option<(String, String)> {
+        let dir = tempfile::tempdir().unwrap();
+        identify(&tar_gz(dir.path(), entries)).unwrap()
+    }
+
+    fn unpacked(path: &Path) -> Result<(tempfile::TempDir, Vec<String>)> {
+        let dest = tempfile::tempdir().unwrap();
+        unpack(path, format(path)?.unwrap(), dest.path())?;
+        let mut files = Vec::new();
+        list(dest.path(), Path::new(""), &mut files);
+        files.sort();
+        Ok((dest, files))
+    }
+
+    fn list(dir: &Path, relative: &Path, files: &mut Vec<String>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let entry = entry.unwrap();
+            let path = relative.join(entry.file_name());
+            if entry.path().is_dir() {
+                list(&entry.path(), &path, files);
+            } else {
+                files.push(path.to_string_lossy().into_owned());
+            }
+        }
+    }
+
+    #[test]
+    fn format_detects_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        let empty = dir.path().join("empty");
+        fs::write(&empty, "").unwrap();
+        assert!(format(&empty).unwrap().is_none());
+        assert!(matches!(format(&tar_gz(dir.path(), &[])).unwrap(), Some(Format::TarGz)));
+        assert!(matches!(format(&zip(dir.path(), &[("a", "")])).unwrap(), Some(Format::Zip)));
+    }
+
+    #[test]
+    fn identify_exported_bundles() {
+        let exported = [("zz/1.0/runtime.wasm", ""), ("zz/1.0/lib/std.zz", "")];
+        assert_eq!(identify_entries(&exported), Some(("zz".to_string(), "1.0".to_string())));
+        let dotted = [("./zz/1.0/runtime.wasm", "")];
+        assert_eq!(identify_entries(&dotted), Some(("zz".to_string(), "1.0".to_string())));
+    }
+
+    #[test]
+    fn identify_rejects_other_layouts() {
+        assert_eq!(identify_entries(&[]), None);
+        assert_eq!(identify_entries(&[("runtime.wasm", ""), ("lib/a/b", "")]), None);
+        assert_eq!(identify_entries(&[("zz/1.0/runtime.wasm", ""), ("zz/2.0/runtime.wasm", "")]), None);
+        assert_eq!(identify_entries(&[("zz/1.0/runtime.wasm", ""), ("zz/sdk.toml", "")]), None);
+        assert_eq!(identify_entries(&[(".zz/1.0/runtime.wasm", "")]), None);
+        assert_eq!(identify_entries(&[("zz/.1.0/runtime.wasm", "")]), None);
+        assert_eq!(identify_entries(&[("zz/1.0/../../runtime.wasm", "")]), None);
+        assert_eq!(identify_entries(&[("/zz/1.0/runtime.wasm", "")]), None);
+    }
+
+    #[test]
+    fn unpack_flat_bundle() {
+        let dir = tempfile::tempdir().unwrap();
+        let bundle = tar_gz(dir.path(), &[("runtime.wasm", "wasm"), ("lib/std.zz", "std")]);
+        let (dest, files) = unpacked(&bundle).unwrap();
+        assert_eq!(files, ["lib/std.zz", "runtime.wasm"]);
+        assert_eq!(fs::read_to_string(dest.path().join("lib/std.zz")).unwrap(), "std");
+    }
+
+    #[test]
+    fn unpack_hoists_exported_bundle() {
+        let dir = tempfile::tempdir().unwrap();
+        let bundle = zip(dir.path(), &[("zz/", ""), ("zz/1.0/", ""), ("zz/1.0/runtime.wasm", ""), ("zz/1.0/zz/1.0/x", "")]);
+        let (_dest, files) = unpacked(&bundle).unwrap();
+        assert_eq!(files, ["runtime.wasm", "zz/1.0/x"]);
+    }
+
+    #[test]
+    fn unpack_keeps_single_directory_bundle() {
+        // A bundle whose runtime sits next to a lone `lib/` is not an export.
+        let dir = tempfile::tempdir().unwrap();
+        let bundle = zip(dir.path(), &[("lib/", ""), ("lib/std/", ""), ("lib/std/os.zz", ""), ("sdk.toml", "")]);
+        let (_dest, files) = unpacked(&bundle).unwrap();
+        assert_eq!(files, ["lib/std/os.zz", "sdk.toml"]);
+    }
+
+    #[test]
+    fn unpack_requires_sdk_at_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let only_lib = tar_gz(dir.path(), &[("lib/a/b/c.zz", "")]);
+        let err = unpacked(&only_lib).unwrap_err().to_string();
+        assert!(err.contains("neither runtime.wasm nor sdk.toml"), "{}", err);
+        let only_export_lib = zip(dir.path(), &[("zz/1.0/lib/c.zz", "")]);
+        assert!(unpacked(&only_export_lib).is_err());
+    }
+
+    #[test]
+    fn unpack_rejects_traversal_in_zip() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("dest");
+        fs::create_dir(&dest).unwrap();
+        let bundle = zip(dir.path(), &[("runtime.wasm", ""), ("../evil", "x")]);
+        let err = unpack(&bundle, Format::Zip, &dest).unwrap_err().to_string();
+        assert!(err.contains("unsafe path '../evil'"), "{}", err);
+        assert!(!dir.path().join("evil").exists());
+    }
+
+    #[test]
+    fn unpack_skips_traversal_in_tar() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("dest");
+        fs::create_dir(&dest).unwrap();
+        let bundle = tar_gz(dir.path(), &[("runtime.wasm", ""), ("../evil", "x")]);
+        unpack(&bundle, Format::TarGz, &dest).unwrap();
+        assert!(!dir.path().join("evil").exists());
+        assert!(dest.join("runtime.wasm").is_file());
+    }
+
+    #[test]
+    fn export_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let sdk = dir.path().join("sdk");
+        fs::create_dir_all(sdk.join("zz/lib")).unwrap();
+        for name in ["runtime.wasm", "runtime.cwasm", ".runtime.wasm.digest", ORIGIN_FILE, "zz/lib/a.zz"] {
+            fs::write(sdk.join(name), name).unwrap();
+        }
+        let output = dir.path().join("zz.tar.gz");
+        export(&sdk, "zz", "1.0", &output).unwrap();
+        assert_eq!(identify(&output).unwrap(), Some(("zz".to_string(), "1.0".to_string())));
+        let (_dest, files) = unpacked(&output).unwrap();
+        assert_eq!(files, ["runtime.wasm", "zz/lib/a.zz"]);
+    }
+}
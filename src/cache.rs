@@ -0,0 +1,288 @@
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::UNIX_EPOCH;
+use wasmtime::{Config, Engine, Module};
+
+use crate::download;
+
+// Compiled code depends on the engine configuration, so every combination of
+// limits that changes it gets its own cache entry.
+#[derive(Clone, Copy, Default)]
+pub struct Profile {
+    pub fuel: bool,
+    pub epoch: bool,
+}
+
+const PROFILES: [Profile; 4] = [
+    Profile { fuel: false, epoch: false },
+    Profile { fuel: true, epoch: false },
+    Profile { fuel: false, epoch: true },
+    Profile { fuel: true, epoch: true },
+];
+
+impl Profile {
+    pub fn engine(self) -> Result<Engine> {
+        let mut config = Config::new();
+        config.consume_fuel(self.fuel);
+        config.epoch_interruption(self.epoch);
+        Engine::new(&config)
+    }
+
+    fn tag(self) -> &'static str {
+        match (self.fuel, self.epoch) {
+            (false, false) => "default",
+            (true, false) => "fuel",
+            (false, true) => "epoch",
+            (true, true) => "fuel+epoch",
+        }
+    }
+}
+
+// Keyed on wasmtime's compatibility hash, which changes with the wasmtime
+// build and the engine settings, and on the runtime's digest.
+fn cache_path(dir: &Path, wasm_path: &Path, digest: &str, engine: &Engine) -> PathBuf {
+    let mut compatibility = DefaultHasher::new();
+    engine.precompile_compatibility_hash().hash(&mut compatibility);
+    let mut hasher = Sha256::new();
+    hasher.update(compatibility.finish().to_le_bytes());
+    hasher.update(digest);
+    let key = format!("{:x}", hasher.finalize());
+    let stem = wasm_path.file_stem().and_then(|s| s.to_str()).unwrap_or("runtime");
+    dir.join(format!("{}.{}.cwasm", stem, &key[..16]))
+}
+
+// Compiled modules are kept next to the runtime, or under `shared` when the
+// SDK directory is read-only (e.g. a system-wide plugin root).
+fn cache_dirs(wasm_path: &Path, digest: &str, shared: &Path) -> [PathBuf; 2] {
+    let local = wasm_path.parent().map(Path::to_path_buf).unwrap_or_default();
+    [local, shared.join(&digest[..16])]
+}
+
+// Hashing a large runtime on every run would cost much of what the cache
+// saves, so its digest is remembered along with the size and modification
+// time it was computed for, and only recomputed when they change. The record
+// is kept next to the runtime, or under `shared` for read-only SDKs.
+pub fn runtime_digest(wasm_path: &Path, shared: &Path) -> Result<String> {
+    let unreadable = |e: std::io::Error| anyhow!("Cannot read runtime '{}': {}", wasm_path.display(), e);
+    let metadata = fs::metadata(wasm_path).map_err(unreadable)?;
+    let modified = metadata.modified().map_err(unreadable)?;
+    let stamp = format!("{} {}", metadata.len(), modified.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos()));
+    let name = wasm_path.file_name().and_then(|n| n.to_str()).unwrap_or("runtime");
+    let path = fs::canonicalize(wasm_path).map_err(unreadable)?;
+    let key = format!("{:x}", Sha256::digest(path.to_string_lossy().as_bytes()));
+    let records = [
+        wasm_path.with_file_name(format!(".{}.digest", name)),
+        shared.join("digests").join(&key[..16]),
+    ];
+    for record in &records {
+        if let Some((recorded, digest)) = fs::read_to_string(record).ok().as_deref().and_then(|r| r.trim().rsplit_once(' ')) {
+            if recorded == stamp && digest.len() == 64 && digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Ok(digest.to_string());
+            }
+        }
+    }
+    let digest = download::sha256_file(wasm_path).map_err(|e| anyhow!("Cannot read runtime '{}': {}", wasm_path.display(), e))?;
+    let content = format!("{} {}\n", stamp, digest);
+    let _ = records.iter().find(|record| {
+        record.parent().is_some_and(|dir| fs::create_dir_all(dir).is_ok()) && fs::write(record, &content).is_ok()
+    });
+    Ok(digest)
+}
+
+// `verified` is the runtime's digest when it has just been checked against a
+// lockfile. Neither the digest record nor compiled code is covered by that
+// check, so the runtime is then only compiled from bytes with that digest,
+// and compiled code is only loaded when `store` recorded writing it.
+pub fn load_module(engine: &Engine, wasm_path: &Path, shared: &Path, verified: Option<&str>) -> Result<Module> {
+    let digest = match verified {
+        Some(digest) => digest.to_string(),
+        None => runtime_digest(wasm_path, shared)?,
+    };
+    for dir in &cache_dirs(wasm_path, &digest, shared) {
+        let cached = cache_path(dir, wasm_path, &digest, engine);
+        if !cached.exists() {
+            continue;
+        }
+        let module = match verified {
+            Some(_) => load_recorded(engine, &cached, shared),
+            // SAFETY: cache entries are only written by `store` from `Module::serialize`,
+            // and wasmtime rejects artifacts built by another version or configuration.
+            None => unsafe { Module::deserialize_file(engine, &cached) }.ok(),
+        };
+        if let Some(module) = module {
+            return Ok(module);
+        }
+    }
+    let bytes = fs::read(wasm_path).map_err(|e| anyhow!("Cannot read runtime '{}': {}", wasm_path.display(), e))?;
+    // Compiling costs far more than hashing, and a stale digest record must
+    // not file the module under another runtime's digest.
+    let digest = format!("{:x}", Sha256::digest(&bytes));
+    if verified.is_some_and(|verified| verified != digest) {
+        return Err(anyhow!("Runtime '{}' changed while it was being loaded", wasm_path.display()));
+    }
+    let module = Module::new(engine, &bytes)?;
+    // If neither directory is writable we just compile on every run.
+    let dirs = cache_dirs(wasm_path, &digest, shared);
+    let _ = dirs.iter().find(|dir| store(&module, dir, wasm_path, &digest, engine, shared).is_ok());
+    Ok(module)
+}
+
+// Lists the SHA-256 of each compiled module `store` has written under a cache
+// file name, kept under `shared` apart from SDK directories. The name covers
+// the runtime's digest and the engine, and `store` is only given modules
+// compiled from bytes with that digest, so any listed module may be loaded.
+fn record_path(cached: &Path, shared: &Path) -> Option<PathBuf> {
+    let name = cached.file_name()?.to_str()?;
+    Some(shared.join("digests").join(format!("{}.sha256", name)))
+}
+
+fn load_recorded(engine: &Engine, cached: &Path, shared: &Path) -> Option<Module> {
+    let recorded = fs::read_to_string(record_path(cached, shared)?).ok()?;
+    let bytes = fs::read(cached).ok()?;
+    let digest = format!("{:x}", Sha256::digest(&bytes));
+    if !recorded.lines().any(|line| line == digest) {
+        return None;
+    }
+    // SAFETY: these are the exact bytes of a module `store` wrote from `Module::serialize`.
+    unsafe { Module::deserialize(engine, &bytes) }.ok()
+}
+
+fn record(cached: &Path, shared: &Path, bytes: &[u8]) -> Result<()> {
+    let path = record_path(cached, shared).ok_or(anyhow!("Invalid cache path '{}'", cached.display()))?;
+    let digest = format!("{:x}", Sha256::digest(bytes));
+    let recorded = fs::read_to_string(&path).unwrap_or_default();
+    if !recorded.lines().any(|line| line == digest) {
+        fs::create_dir_all(shared.join("digests"))?;
+        fs::OpenOptions::new().create(true).append(true).open(&path)?.write_all(format!("{}\n", digest).as_bytes())?;
+    }
+    Ok(())
+}
+
+// Names of the engine profiles that already have a compiled module cached
+// for a runtime with the given digest.
+pub fn cached_profiles(wasm_path: &Path, digest: &str, shared: &Path) -> Vec<&'static str> {
+    let dirs = cache_dirs(wasm_path, digest, shared);
+    engines()
+        .into_iter()
+        .filter(|(_, engine)| dirs.iter().any(|dir| cache_path(dir, wasm_path, digest, engine).exists()))
+        .map(|(profile, _)| profile.tag())
+        .collect()
+}
+
+fn engines() -> Vec<(Profile, Engine)> {
+    PROFILES
+        .iter()
+        .filter_map(|p| p.engine().ok().map(|engine| (*p, engine)))
+        .collect()
+}
+
+fn store(module: &Module, dir: &Path, wasm_path: &Path, digest: &str, engine: &Engine, shared: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let cached = cache_path(dir, wasm_path, digest, engine);
+    let tmp = cached.with_extension(format!("cwasm.{}.tmp", process::id()));
+    let bytes = module.serialize()?;
+    fs::write(&tmp, &bytes)?;
+    fs::rename(&tmp, &cached)?;
+    // Without a record the module is only used by runs that are not locked.
+    let _ = record(&cached, shared, &bytes);
+
+    let keep: Vec<PathBuf> = engines().iter().map(|(_, e)| cache_path(dir, wasm_path, digest, e)).collect();
+    let stem = wasm_path.file_stem().and_then(|s| s.to_str()).unwrap_or("runtime");
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        let stale = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|n| n.starts_with(&format!("{}.", stem)) && n.ends_with(".cwasm"));
+        if stale && !keep.contains(&path) {
+            let _ = fs::remove_file(&path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUNTIME: &str = r#"(module (func (export "_start")))"#;
+
+    fn runtime(dir: &Path) -> (PathBuf, String) {
+        let sdk = dir.join("sdk");
+        fs::create_dir_all(&sdk).unwrap();
+        let wasm_path = sdk.join("runtime.wasm");
+        fs::write(&wasm_path, RUNTIME).unwrap();
+        (wasm_path, format!("{:x}", Sha256::digest(RUNTIME)))
+    }
+
+    fn cached_module(wasm_path: &Path) -> PathBuf {
+        fs::read_dir(wasm_path.parent().unwrap())
+            .unwrap()
+            .flatten()
+            .map(|e| e.path())
+            .find(|p| p.extension().is_some_and(|e| e == "cwasm"))
+            .unwrap()
+    }
+
+    #[test]
+    fn locked_load_ignores_unrecorded_modules() {
+        let dir = tempfile::tempdir().unwrap();
+        let (wasm_path, digest) = runtime(dir.path());
+        let shared = dir.path().join("cache");
+        let engine = Profile::default().engine().unwrap();
+        load_module(&engine, &wasm_path, &shared, None).unwrap();
+        let cached = cached_module(&wasm_path);
+        let compiled = fs::read(&cached).unwrap();
+        assert!(load_recorded(&engine, &cached, &shared).is_some());
+
+        fs::write(&cached, b"not a module").unwrap();
+        assert!(load_recorded(&engine, &cached, &shared).is_none());
+        load_module(&engine, &wasm_path, &shared, Some(&digest)).unwrap();
+        assert_eq!(fs::read(&cached).unwrap(), compiled);
+    }
+
+    #[test]
+    fn locked_load_rejects_a_changed_runtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let (wasm_path, _) = runtime(dir.path());
+        let engine = Profile::default().engine().unwrap();
+        let result = load_module(&engine, &wasm_path, &dir.path().join("cache"), Some(&"0".repeat(64)));
+        let err = result.err().unwrap();
+        assert!(err.to_string().contains("changed while it was being loaded"), "{}", err);
+    }
+
+    #[test]
+    fn stale_digest_record_does_not_misfile_modules() {
+        let dir = tempfile::tempdir().unwrap();
+        let (wasm_path, digest) = runtime(dir.path());
+        let shared = dir.path().join("cache");
+        let engine = Profile::default().engine().unwrap();
+        runtime_digest(&wasm_path, &shared).unwrap();
+        let record = wasm_path.with_file_name(".runtime.wasm.digest");
+        let content = fs::read_to_string(&record).unwrap().replace(&digest, &"0".repeat(64));
+        fs::write(&record, content).unwrap();
+        load_module(&engine, &wasm_path, &shared, None).unwrap();
+        assert_eq!(cached_module(&wasm_path), cache_path(wasm_path.parent().unwrap(), &wasm_path, &digest, &engine));
+    }
+
+    #[test]
+    fn malformed_digest_record_is_rehashed() {
+        let dir = tempfile::tempdir().unwrap();
+        let (wasm_path, digest) = runtime(dir.path());
+        let shared = dir.path().join("cache");
+        let engine = Profile::default().engine().unwrap();
+        runtime_digest(&wasm_path, &shared).unwrap();
+        let record = wasm_path.with_file_name(".runtime.wasm.digest");
+        let content = fs::read_to_string(&record).unwrap().replace(&digest, "abc");
+        fs::write(&record, content).unwrap();
+        assert_eq!(runtime_digest(&wasm_path, &shared).unwrap(), digest);
+        fs::write(&record, fs::read_to_string(&record).unwrap().replace(&digest, "abc")).unwrap();
+        load_module(&engine, &wasm_path, &shared, None).unwrap();
+    }
+}
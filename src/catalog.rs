@@ -0,0 +1,127 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::manifest::ManifestMount;
+
+pub const CATALOG_FILE: &str = "catalog.toml";
+pub const SYSTEM_CATALOG: &str = "/etc/rchidrun/catalog.toml";
+const BUNDLED: &str = include_str!("catalog.toml");
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct CatalogFile {
+    language: BTreeMap<String, Entry>,
+}
+
+// How to install and run one language. `args`, `env` and `mounts` make up the
+// manifest of SDKs installed without an `sdk.toml` of their own.
+#[derive(Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Entry {
+    pub description: Option<String>,
+    pub wasmer: Option<String>,
+    pub url: Option<String>,
+    pub sha256: Option<String>,
+    pub aliases: Vec<String>,
+    pub extensions: Vec<String>,
+    pub interpreter: Option<String>,
+    pub args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    pub mounts: Vec<ManifestMount>,
+}
+
+impl Entry {
+    pub fn has_source(&self) -> bool {
+        self.wasmer.is_some() || self.url.is_some()
+    }
+
+    pub fn source(&self) -> Option<&str> {
+        self.wasmer.as_deref().or(self.url.as_deref())
+    }
+}
+
+// The bundled catalog with every configured layer applied on top; an entry
+// in a later layer replaces the earlier entry of the same name.
+#[derive(Default)]
+pub struct Catalog {
+    pub languages: BTreeMap<String, Entry>,
+}
+
+impl Catalog {
+    pub fn new() -> Result<Catalog> {
+        let mut catalog = Catalog::default();
+        catalog.merge(BUNDLED, "bundled catalog")?;
+        Ok(catalog)
+    }
+
+    pub fn merge(&mut self, content: &str, name: &str) -> Result<()> {
+        let file: CatalogFile = toml::from_str(content).map_err(|e| anyhow!("Invalid catalog '{}': {}", name, e))?;
+        for (language, entry) in file.language {
+            if entry.wasmer.is_some() && entry.url.is_some() {
+                return Err(anyhow!(
+                    "Invalid catalog '{}': [language.{}] sets both wasmer and url",
+                    name,
+                    language
+                ));
+            }
+            self.languages.insert(language.to_lowercase(), entry);
+        }
+        Ok(())
+    }
+
+    // Merges a catalog file if it exists.
+    pub fn merge_file(&mut self, path: &Path) -> Result<()> {
+        match fs::read_to_string(path) {
+            Ok(content) => self.merge(&content, &path.display().to_string()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(anyhow!("Cannot read catalog '{}': {}", path.display(), e)),
+        }
+    }
+
+    pub fn get(&self, language: &str) -> Option<&Entry> {
+        self.languages.get(language)
+    }
+
+    pub fn alias(&self, name: &str) -> Option<&str> {
+        self.languages
+            .iter()
+            .find(|(_, entry)| entry.aliases.iter().any(|a| a.eq_ignore_ascii_case(name)))
+            .map(|(language, _)| language.as_str())
+    }
+
+    pub fn extension_language(&self, extension: &str) -> Option<&str> {
+        self.languages
+            .iter()
+            .find(|(_, entry)| {
+                entry
+                    .extensions
+                    .iter()
+                    .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(extension))
+            })
+            .map(|(language, _)| language.as_str())
+    }
+
+    pub fn interpreter_language(&self, interpreter: &str) -> Option<&str> {
+        self.languages
+            .iter()
+            .find(|(_, entry)| entry.interpreter.as_deref() == Some(interpreter))
+            .map(|(language, _)| language.as_str())
+    }
+
+    // Entries whose name, aliases, extensions, description or source contain
+    // `term`, ignoring case.
+    pub fn search(&self, term: &str) -> Vec<(&String, &Entry)> {
+        let term = term.to_lowercase();
+        self.languages
+            .iter()
+            .filter(|(language, entry)| {
+                let fields = [Some(language.as_str()), entry.description.as_deref(), entry.source()];
+                fields.iter().flatten().any(|f| f.to_lowercase().contains(&term))
+                    || entry.aliases.iter().chain(&entry.extensions).any(|f| f.to_lowercase().contains(&term))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,103 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// User configuration read from `~/.rchidrun/config.toml`. Every section is
+// optional and a missing file behaves like an empty one.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub extensions: HashMap<String, String>,
+    pub aliases: HashMap<String, String>,
+    pub registry: Option<String>,
+    pub catalog: Option<String>,
+    pub trusted_keys: Option<PathBuf>,
+    pub download: DownloadConfig,
+}
+
+// Overrides for the downloader's defaults; sizes and durations use the same
+// syntax as the `run` flags.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct DownloadConfig {
+    pub retries: Option<u32>,
+    pub timeout: Option<String>,
+    pub connect_timeout: Option<String>,
+    pub max_size: Option<String>,
+}
+
+// Extensions may be written as `py` or `.py`, in any case; keys are stored
+// without the dot and lowercased so that each extension has exactly one entry.
+pub fn normalize_extensions(extensions: HashMap<String, String>) -> std::result::Result<HashMap<String, String>, String> {
+    let mut normalized = HashMap::new();
+    for (extension, language) in extensions {
+        let key = extension.trim_start_matches('.').to_ascii_lowercase();
+        if key.is_empty() {
+            return Err(format!("extension '{}' is empty", extension));
+        }
+        if normalized.insert(key.clone(), language).is_some() {
+            return Err(format!("extension '{}' is mapped more than once", key));
+        }
+    }
+    Ok(normalized)
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config> {
+        let mut config: Config = match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content).map_err(|e| anyhow!("Invalid config '{}': {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(anyhow!("Cannot read config '{}': {}", path.display(), e)),
+        };
+        config.extensions = normalize_extensions(std::mem::take(&mut config.extensions))
+            .map_err(|e| anyhow!("Invalid config '{}': {}", path.display(), e))?;
+        Ok(config)
+    }
+
+    pub fn extension_language(&self, extension: &str) -> Option<&str> {
+        self.extensions.get(&extension.trim_start_matches('.').to_ascii_lowercase()).map(String::as_str)
+    }
+
+    pub fn alias(&self, name: &str) -> Option<&str> {
+        self.aliases
+            .iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+            .map(|(_, language)| language.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extensions(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(e, l)| (e.to_string(), l.to_string())).collect()
+    }
+
+    #[test]
+    fn normalize_extensions_strips_dot_and_case() {
+        let normalized = normalize_extensions(extensions(&[(".PY", "python"), ("rb", "ruby")])).unwrap();
+        assert_eq!(normalized, extensions(&[("py", "python"), ("rb", "ruby")]));
+        assert!(normalize_extensions(HashMap::new()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn normalize_extensions_rejects_duplicates_and_empty_keys() {
+        let err = normalize_extensions(extensions(&[(".py", "python"), ("PY", "other")])).unwrap_err();
+        assert_eq!(err, "extension 'py' is mapped more than once");
+        assert!(normalize_extensions(extensions(&[("", "python")])).is_err());
+        assert!(normalize_extensions(extensions(&[(".", "python")])).is_err());
+    }
+
+    #[test]
+    fn extension_language_ignores_dot_and_case() {
+        let config = Config {
+            extensions: extensions(&[("py", "python")]),
+            ..Config::default()
+        };
+        assert_eq!(config.extension_language(".Py"), Some("python"));
+        assert_eq!(config.extension_language("rb"), None);
+    }
+}
@@ -0,0 +1,281 @@
+use anyhow::{anyhow, Result};
+use fs2::FileExt;
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, RANGE};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+// Limits applied to every download, set from the `[download]` section of
+// the config file.
+pub struct Settings {
+    pub retries: u32,
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    pub max_size: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            retries: 3,
+            timeout: Duration::from_secs(300),
+            connect_timeout: Duration::from_secs(30),
+            max_size: 1 << 30,
+        }
+    }
+}
+
+// Partial downloads are kept in `dir` as `<key>.part`, next to the validator
+// (ETag or Last-Modified) needed to resume them with a Range request.
+pub struct Downloader {
+    pub dir: PathBuf,
+    pub settings: Settings,
+}
+
+// A finished download; the file is deleted when it is dropped, so nothing is
+// left behind once it has been installed or rejected.
+pub struct Download {
+    pub path: PathBuf,
+    pub etag: Option<String>,
+}
+
+impl Drop for Download {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+pub enum Failed {
+    Retry(anyhow::Error),
+    Fatal(anyhow::Error),
+}
+
+impl Downloader {
+    pub fn client(&self) -> Result<Client> {
+        Ok(Client::builder()
+            .timeout(self.settings.timeout)
+            .connect_timeout(self.settings.connect_timeout)
+            .build()?)
+    }
+
+    // Runs `attempt` until it succeeds or fails fatally, retrying up to
+    // `settings.retries` times with exponential backoff. `what` names the
+    // operation in the retry messages.
+    pub fn retrying<T>(&self, what: &str, mut attempt: impl FnMut() -> Result<T, Failed>) -> Result<T> {
+        let mut retry = 0;
+        loop {
+            match attempt() {
+                Ok(value) => return Ok(value),
+                Err(Failed::Retry(e)) if retry < self.settings.retries => {
+                    let delay = Duration::from_secs(1 << retry.min(5));
+                    retry += 1;
+                    eprintln!(
+                        "{} failed ({}); retrying in {}s ({}/{})",
+                        what,
+                        e,
+                        delay.as_secs(),
+                        retry,
+                        self.settings.retries
+                    );
+                    thread::sleep(delay);
+                }
+                Err(Failed::Retry(e)) | Err(Failed::Fatal(e)) => return Err(e),
+            }
+        }
+    }
+
+    // Returns None when the server reports that `etag` is still current.
+    pub fn fetch(&self, url: &str, etag: Option<&str>) -> Result<Option<Download>> {
+        fs::create_dir_all(&self.dir)?;
+        let key = format!("{:x}", Sha256::digest(url.as_bytes()));
+        if let Some(path) = file_url_path(url) {
+            // Local files are copied, so `file://` URLs work without a network.
+            let size = fs::metadata(path).map_err(|e| anyhow!("Cannot read '{}': {}", path.display(), e))?.len();
+            if let Err(Failed::Retry(e) | Failed::Fatal(e)) = self.check_size(size) {
+                return Err(anyhow!("Failed to download {}: {}", url, e));
+            }
+            let copy = self.dir.join(format!(".download-{}-{}", &key[..16], process::id()));
+            fs::copy(path, &copy).map_err(|e| anyhow!("Cannot read '{}': {}", path.display(), e))?;
+            return Ok(Some(Download { path: copy, etag: None }));
+        }
+        let client = self.client()?;
+        let part = self.dir.join(format!("{}.part", &key[..16]));
+        // Every process fetching this URL resumes the same partial file, so
+        // they take turns rather than appending to it at the same time.
+        let lock = File::options()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(part.with_extension("lock"))?;
+        if lock.try_lock_exclusive().is_err() {
+            eprintln!("Waiting for another download of {} to finish...", url);
+            lock.lock_exclusive()?;
+        }
+        self.retrying(&format!("Download of {}", url), || self.attempt(&client, url, etag, &part))
+            .map_err(|e| anyhow!("Failed to download {}: {}", url, e))
+    }
+
+    fn attempt(&self, client: &Client, url: &str, etag: Option<&str>, part: &Path) -> Result<Option<Download>, Failed> {
+        let validator_path = part.with_extension("part.validator");
+        let offset = fs::metadata(part).map(|m| m.len()).unwrap_or(0);
+        let validator = fs::read_to_string(&validator_path).ok();
+        let mut request = client.get(url);
+        if let Some(etag) = etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        let resuming = offset > 0 && validator.is_some();
+        if let (true, Some(validator)) = (resuming, &validator) {
+            request = request.header(RANGE, format!("bytes={}-", offset)).header(IF_RANGE, validator.as_str());
+        }
+        let resp = request.send().map_err(|e| {
+            if e.is_builder() {
+                Failed::Fatal(e.into())
+            } else {
+                Failed::Retry(e.into())
+            }
+        })?;
+        let status = resp.status();
+        if status == StatusCode::NOT_MODIFIED {
+            discard(part);
+            return Ok(None);
+        }
+        if status == StatusCode::RANGE_NOT_SATISFIABLE {
+            discard(part);
+            return Err(Failed::Retry(anyhow!("server rejected the resume request")));
+        }
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(Failed::Retry(anyhow!("HTTP {}", status)));
+        }
+        if !status.is_success() {
+            discard(part);
+            return Err(Failed::Fatal(anyhow!("HTTP {}", status)));
+        }
+        let content_type = header(&resp, CONTENT_TYPE.as_str()).unwrap_or_default();
+        if content_type.starts_with("text/html") {
+            discard(part);
+            return Err(Failed::Fatal(anyhow!("server returned an HTML page instead of a file")));
+        }
+        let resumed = status == StatusCode::PARTIAL_CONTENT;
+        if resumed
+            && !header(&resp, CONTENT_RANGE.as_str()).is_some_and(|r| r.starts_with(&format!("bytes {}-", offset)))
+        {
+            discard(part);
+            return Err(Failed::Retry(anyhow!("server returned an unexpected range")));
+        }
+        let start = if resumed { offset } else { 0 };
+        let total = resp.content_length().map(|len| start + len);
+        if let Some(Err(e)) = total.map(|total| self.check_size(total)) {
+            discard(part);
+            return Err(e);
+        }
+        let etag_header = header(&resp, ETAG.as_str());
+        if !resumed {
+            match etag_header.clone().or_else(|| header(&resp, LAST_MODIFIED.as_str())) {
+                Some(validator) => fs::write(&validator_path, validator).map_err(|e| Failed::Fatal(e.into()))?,
+                None => {
+                    let _ = fs::remove_file(&validator_path);
+                }
+            }
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resumed)
+            .truncate(!resumed)
+            .open(part)
+            .map_err(|e| Failed::Fatal(e.into()))?;
+        let written = self.copy(resp, &mut file, start, total, part)?;
+        if total.is_some_and(|total| written != total) {
+            return Err(Failed::Retry(anyhow!("connection closed after {} bytes", written)));
+        }
+        let name = part.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        let path = self.dir.join(format!(".download-{}-{}", name, process::id()));
+        fs::rename(part, &path).map_err(|e| Failed::Fatal(e.into()))?;
+        let _ = fs::remove_file(&validator_path);
+        Ok(Some(Download { path, etag: etag_header }))
+    }
+
+    fn copy(
+        &self,
+        mut resp: Response,
+        file: &mut File,
+        start: u64,
+        total: Option<u64>,
+        part: &Path,
+    ) -> Result<u64, Failed> {
+        let progress = if io::stderr().is_terminal() {
+            let bar = match total {
+                Some(total) => ProgressBar::new(total),
+                None => ProgressBar::new_spinner(),
+            };
+            if let Ok(style) = ProgressStyle::with_template("{bytes}/{total_bytes} [{bar:30}] {bytes_per_sec}, {eta}") {
+                bar.set_style(style.progress_chars("=> "));
+            }
+            bar
+        } else {
+            ProgressBar::hidden()
+        };
+        progress.set_position(start);
+        let mut written = start;
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let read = match resp.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    progress.abandon();
+                    return Err(Failed::Retry(e.into()));
+                }
+            };
+            written += read as u64;
+            if let Err(e) = self.check_size(written) {
+                progress.abandon();
+                discard(part);
+                return Err(e);
+            }
+            file.write_all(&buffer[..read]).map_err(|e| Failed::Fatal(e.into()))?;
+            progress.set_position(written);
+        }
+        progress.finish_and_clear();
+        Ok(written)
+    }
+
+    fn check_size(&self, size: u64) -> Result<(), Failed> {
+        if size > self.settings.max_size {
+            return Err(Failed::Fatal(anyhow!(
+                "file is larger than the {} byte limit (download.max-size)",
+                self.settings.max_size
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn header(resp: &Response, name: &str) -> Option<String> {
+    resp.headers().get(name).and_then(|v| v.to_str().ok()).map(String::from)
+}
+
+fn discard(part: &Path) {
+    let _ = fs::remove_file(part);
+    let _ = fs::remove_file(part.with_extension("part.validator"));
+}
+
+// The path named by a `file://` URL; `file://localhost/` is accepted too.
+pub fn file_url_path(url: &str) -> Option<&Path> {
+    let path = url.strip_prefix("file://")?;
+    Some(Path::new(path.strip_prefix("localhost").unwrap_or(path)))
+}
+
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
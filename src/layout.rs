@@ -0,0 +1,339 @@
+use anyhow::{anyhow, Result};
+use semver::Version;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::download;
+use crate::manifest::{Manifest, MANIFEST_FILE};
+use crate::origin::{Origin, Source, ORIGIN_FILE};
+use crate::package::CONTAINER_FILE;
+
+// Each language directory holds one subdirectory per installed version and a
+// `default` file naming the version used when none is requested.
+pub const DEFAULT_FILE: &str = "default";
+pub const UNVERSIONED: &str = "unversioned";
+
+// Languages and versions name directories under the plugin root, so each
+// must be a single plain path component.
+pub fn check_name(kind: &str, name: &str) -> Result<()> {
+    let mut components = Path::new(name).components();
+    let plain = matches!((components.next(), components.next()), (Some(Component::Normal(_)), None));
+    if !plain || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(anyhow!(
+            "Invalid {} '{}': it must be a plain name, without path separators or a leading '.'",
+            kind,
+            name
+        ));
+    }
+    Ok(())
+}
+
+fn parse_version(version: &str) -> Option<Version> {
+    let version = version.trim_start_matches('v');
+    Version::parse(version).ok().or_else(|| match version.split('.').count() {
+        1 => Version::parse(&format!("{}.0.0", version)).ok(),
+        2 => Version::parse(&format!("{}.0", version)).ok(),
+        _ => None,
+    })
+}
+
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    match (parse_version(a), parse_version(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Greater,
+        (None, Some(_)) => Ordering::Less,
+        (None, None) => a.cmp(b),
+    }
+}
+
+// `3.11` selects any 3.11.x; an exact directory name always wins.
+pub fn matches(version: &str, requested: &str) -> bool {
+    version == requested || version.trim_start_matches('v').starts_with(&format!("{}.", requested.trim_start_matches('v')))
+}
+
+// Turns the version in `python@3.11` into a registry constraint with the same
+// meaning as `matches`: a full version is exact, a partial one a prefix.
+pub fn constraint(requested: &str) -> String {
+    let version = requested.strip_prefix('v').unwrap_or(requested);
+    if version.starts_with(|c: char| c.is_ascii_digit()) {
+        // Prerelease and build suffixes may contain dots of their own.
+        let core = version.split(['-', '+']).next().unwrap_or(version);
+        match core.split('.').count() {
+            3 => format!("={}", version),
+            _ => format!("~{}", version),
+        }
+    } else {
+        requested.to_string()
+    }
+}
+
+pub fn versions(lang_dir: &Path) -> Vec<String> {
+    let mut versions = Vec::new();
+    if let Ok(entries) = fs::read_dir(lang_dir) {
+        for entry in entries.flatten() {
+            if entry.path().is_dir() {
+                if let Some(name) = entry.file_name().to_str() {
+                    if !name.starts_with('.') {
+                        versions.push(name.to_string());
+                    }
+                }
+            }
+        }
+    }
+    versions.sort_by(|a, b| compare_versions(a, b));
+    versions
+}
+
+pub fn default_version(lang_dir: &Path) -> Option<String> {
+    let versions = versions(lang_dir);
+    fs::read_to_string(lang_dir.join(DEFAULT_FILE))
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| versions.contains(v))
+        .or_else(|| versions.last().cloned())
+}
+
+pub fn set_default(lang_dir: &Path, version: &str) -> Result<()> {
+    fs::write(lang_dir.join(DEFAULT_FILE), format!("{}\n", version))?;
+    Ok(())
+}
+
+pub fn has_default(lang_dir: &Path) -> bool {
+    fs::read_to_string(lang_dir.join(DEFAULT_FILE)).is_ok_and(|v| lang_dir.join(v.trim()).is_dir())
+}
+
+// Picks the installed version directory for a request: the default when none
+// is given, otherwise the directory of that exact name or else the newest
+// version matching it.
+pub fn resolve(lang_dir: &Path, requested: Option<&str>) -> Option<String> {
+    match requested {
+        None => default_version(lang_dir),
+        Some(requested) => {
+            let versions = versions(lang_dir);
+            let exact = versions.iter().find(|v| *v == requested);
+            exact.or_else(|| versions.iter().rev().find(|v| matches(v, requested))).cloned()
+        }
+    }
+}
+
+// Installs made before versioned layouts put the runtime directly in the
+// language directory.
+pub fn is_flat(lang_dir: &Path) -> bool {
+    [MANIFEST_FILE, ORIGIN_FILE, CONTAINER_FILE, "runtime.wasm"]
+        .iter()
+        .any(|name| lang_dir.join(name).is_file())
+}
+
+fn flat_version(lang_dir: &Path) -> String {
+    if let Ok(Some(Manifest {
+        version: Some(version), ..
+    })) = Manifest::load(lang_dir)
+    {
+        if check_name("version", &version).is_err() {
+            return UNVERSIONED.to_string();
+        }
+        return version;
+    }
+    match Origin::load(lang_dir) {
+        Ok(Some(Origin {
+            source: Source::Wasmer { version, .. },
+            ..
+        })) => version,
+        _ => UNVERSIONED.to_string(),
+    }
+}
+
+// SHA-256 of every file of an SDK, keyed by its path inside the SDK
+// directory. The origin record and the caches kept next to the runtime change
+// after install and are left out.
+pub fn digests(sdk_path: &Path) -> Result<BTreeMap<String, String>> {
+    let mut digests = BTreeMap::new();
+    collect_digests(sdk_path, Path::new(""), &mut digests)?;
+    Ok(digests)
+}
+
+fn collect_digests(dir: &Path, relative: &Path, digests: &mut BTreeMap<String, String>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let top = relative.as_os_str().is_empty();
+        let text = name.to_string_lossy();
+        let cache = text.ends_with(".cwasm") || (text.starts_with('.') && text.ends_with(".digest"));
+        if cache || (top && (text == ORIGIN_FILE || text.starts_with('.'))) {
+            continue;
+        }
+        let path = relative.join(&name);
+        if entry.path().is_dir() {
+            collect_digests(&entry.path(), &path, digests)?;
+        } else {
+            digests.insert(path.to_string_lossy().into_owned(), download::sha256_file(&entry.path())?);
+        }
+    }
+    Ok(())
+}
+
+// Moves a fully prepared SDK directory into place, replacing any existing
+// install of the same version.
+pub fn replace(staged: &Path, target: &Path) -> Result<()> {
+    if !target.exists() {
+        fs::rename(staged, target)?;
+        return Ok(());
+    }
+    let name = target.file_name().ok_or(anyhow!("Invalid SDK directory '{}'", target.display()))?;
+    let old = target.with_file_name(format!(".{}.old", name.to_string_lossy()));
+    if old.exists() {
+        fs::remove_dir_all(&old)?;
+    }
+    fs::rename(target, &old)?;
+    if let Err(e) = fs::rename(staged, target) {
+        let _ = fs::rename(&old, target);
+        return Err(e.into());
+    }
+    fs::remove_dir_all(&old)?;
+    Ok(())
+}
+
+// Removes what an interrupted install left behind. Only safe while holding
+// the plugin directory lock.
+pub fn remove_stale(lang_dir: &Path) {
+    if let Ok(entries) = fs::read_dir(lang_dir) {
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with(".staging-") || (name.starts_with('.') && name.ends_with(".old")) {
+                let _ = fs::remove_dir_all(entry.path());
+            }
+        }
+    }
+}
+
+pub fn migrate(lang_dir: &Path) -> Result<PathBuf> {
+    let version = flat_version(lang_dir);
+    let name = lang_dir.file_name().ok_or(anyhow!("Invalid SDK directory '{}'", lang_dir.display()))?;
+    let staging = lang_dir.with_file_name(format!(".{}.migrating", name.to_string_lossy()));
+    fs::rename(lang_dir, &staging)?;
+    fs::create_dir(lang_dir)?;
+    let target = lang_dir.join(&version);
+    fs::rename(&staging, &target)?;
+    set_default(lang_dir, &version)?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sdk_dir(versions: &[&str], default: Option<&str>) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for version in versions {
+            fs::create_dir(dir.path().join(version)).unwrap();
+        }
+        if let Some(default) = default {
+            set_default(dir.path(), default).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn matches_full_and_partial_versions() {
+        assert!(matches("3.11.4", "3.11.4"));
+        assert!(matches("3.11.4", "3.11"));
+        assert!(matches("3.11.4", "3"));
+        assert!(matches("v3.11.4", "3.11"));
+        assert!(matches("3.11.4", "v3.11"));
+        assert!(matches(UNVERSIONED, UNVERSIONED));
+        assert!(!matches("3.110.0", "3.11"));
+        assert!(!matches("3.11.4", "3.11.4.1"));
+        assert!(!matches("3.11.4", ""));
+    }
+
+    #[test]
+    fn matches_prerelease_versions() {
+        assert!(matches("3.12.0-rc1", "3.12"));
+        assert!(matches("3.12.0-rc1", "3.12.0-rc1"));
+        assert!(!matches("3.12.0-rc1", "3.12.0"));
+    }
+
+    #[test]
+    fn constraint_follows_matches() {
+        assert_eq!(constraint("3.11.4"), "=3.11.4");
+        assert_eq!(constraint("3.11"), "~3.11");
+        assert_eq!(constraint("3"), "~3");
+        assert_eq!(constraint("v3.11"), "~3.11");
+        assert_eq!(constraint("3.12.0-rc1"), "=3.12.0-rc1");
+        assert_eq!(constraint("3.12.0-rc.1"), "=3.12.0-rc.1");
+        assert_eq!(constraint("1.0.0+build.5"), "=1.0.0+build.5");
+        assert_eq!(constraint("^3.11"), "^3.11");
+        assert_eq!(constraint("latest"), "latest");
+        assert_eq!(constraint(""), "");
+    }
+
+    #[test]
+    fn compare_versions_orders_prereleases_first() {
+        assert_eq!(compare_versions("3.12.0-rc1", "3.12.0"), Ordering::Less);
+        assert_eq!(compare_versions("3.12.0-rc.2", "3.12.0-rc.10"), Ordering::Less);
+        assert_eq!(compare_versions("3.9", "3.10.1"), Ordering::Less);
+        assert_eq!(compare_versions("v3", "3.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions(UNVERSIONED, "0.1"), Ordering::Less);
+    }
+
+    #[test]
+    fn versions_are_sorted_and_skip_hidden_entries() {
+        let dir = sdk_dir(&["3.10.2", "3.9.1", ".staging-1", "3.12.0-rc1"], Some("3.9.1"));
+        assert_eq!(versions(dir.path()), ["3.9.1", "3.10.2", "3.12.0-rc1"]);
+        assert!(versions(&dir.path().join("missing")).is_empty());
+    }
+
+    #[test]
+    fn resolve_uses_default_then_newest_match() {
+        let dir = sdk_dir(&["3.11.2", "3.11.10", "3.12.0-rc1", "2.7.18"], Some("3.11.2"));
+        assert_eq!(resolve(dir.path(), None).as_deref(), Some("3.11.2"));
+        assert_eq!(resolve(dir.path(), Some("3.11")).as_deref(), Some("3.11.10"));
+        assert_eq!(resolve(dir.path(), Some("3")).as_deref(), Some("3.12.0-rc1"));
+        assert_eq!(resolve(dir.path(), Some("2.7.18")).as_deref(), Some("2.7.18"));
+        assert_eq!(resolve(dir.path(), Some("3.1")), None);
+    }
+
+    #[test]
+    fn resolve_prefers_an_exact_directory() {
+        let dir = sdk_dir(&["1.0", "1.0.5"], None);
+        assert_eq!(resolve(dir.path(), Some("1.0")).as_deref(), Some("1.0"));
+        assert_eq!(resolve(dir.path(), Some("1")).as_deref(), Some("1.0.5"));
+    }
+
+    #[test]
+    fn resolve_ignores_a_stale_default() {
+        let dir = sdk_dir(&["1.0.0", "1.2.0"], Some("0.9.0"));
+        assert_eq!(resolve(dir.path(), None).as_deref(), Some("1.2.0"));
+        assert!(!has_default(dir.path()));
+        assert_eq!(resolve(sdk_dir(&[], None).path(), None), None);
+    }
+
+    #[test]
+    fn check_name_accepts_plain_names() {
+        for name in ["python", "3.12.0", "3.12.0-rc.1", "^3.11", "c++", "unversioned"] {
+            assert!(check_name("version", name).is_ok(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn check_name_rejects_traversal() {
+        for name in ["", ".", "..", "../x", "x/..", "a/b", "/abs", "a\\b", "..\\x", ".hidden", "x/"] {
+            assert!(check_name("language", name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn digests_skip_caches_and_origin() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("lib/.git")).unwrap();
+        for name in ["runtime.wasm", "runtime.cwasm", ".runtime.wasm.digest", ORIGIN_FILE, ".lock", "lib/.git/x"] {
+            fs::write(dir.path().join(name), name).unwrap();
+        }
+        let digests = digests(dir.path()).unwrap();
+        assert_eq!(digests.keys().collect::<Vec<_>>(), ["lib/.git/x", "runtime.wasm"]);
+        assert_eq!(digests["runtime.wasm"], download::sha256_file(&dir.path().join("runtime.wasm")).unwrap());
+    }
+}
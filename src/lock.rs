@@ -0,0 +1,57 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::layout;
+use crate::origin::Source;
+
+pub const LOCK_FILE: &str = "rchidrun.lock";
+
+const HEADER: &str = "# Generated by `rchidrun lock`. Do not edit by hand.\n\n";
+
+// Exact SDK versions, sources and hashes a project was locked to. Lives next
+// to `.rchidrun.toml` and is meant to be committed with it.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Lock {
+    #[serde(default, rename = "sdk")]
+    pub sdks: Vec<LockedSdk>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LockedSdk {
+    pub language: String,
+    pub version: String,
+    pub sha256: String,
+    pub source: Source,
+}
+
+impl Lock {
+    pub fn load(dir: &Path) -> Result<Option<Lock>> {
+        let path = dir.join(LOCK_FILE);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(anyhow!("Cannot read lockfile '{}': {}", path.display(), e)),
+        };
+        let lock: Lock =
+            toml::from_str(&content).map_err(|e| anyhow!("Invalid lockfile '{}': {}", path.display(), e))?;
+        for sdk in &lock.sdks {
+            layout::check_name("language", &sdk.language)
+                .and_then(|()| layout::check_name("version", &sdk.version))
+                .map_err(|e| anyhow!("Invalid lockfile '{}': {}", path.display(), e))?;
+        }
+        Ok(Some(lock))
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        fs::write(dir.join(LOCK_FILE), format!("{}{}", HEADER, toml::to_string_pretty(self)?))?;
+        Ok(())
+    }
+
+    pub fn get(&self, language: &str) -> Option<&LockedSdk> {
+        self.sdks.iter().find(|sdk| sdk.language == language)
+    }
+}
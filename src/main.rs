@@ -1,18 +1,68 @@
-use anyhow::{anyhow, Result};
-use clap::{Parser, Subcommand};
-use reqwest::blocking::get;
-use std::collections::HashMap;
+use anyhow::{anyhow, Context, Result};
+use clap::{Args, Parser, Subcommand};
+use serde::Serialize;
+use fs2::FileExt;
+use std::collections::BTreeMap;
 use std::env;
+use std::fmt;
 use std::fs::{self, File};
-use std::io::{self, copy, Write};
-use std::path::PathBuf;
-use std::process::Command;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
 use wasmtime::*;
-use wasmtime_wasi::WasiCtxBuilder;
+use wasmtime_wasi::{ambient_authority, Dir, I32Exit, WasiCtx, WasiCtxBuilder};
+
+mod bundle;
+mod cache;
+mod catalog;
+mod config;
+mod download;
+mod layout;
+mod lock;
+mod manifest;
+mod origin;
+mod package;
+mod project;
+mod readonly;
+mod registry;
+mod verify;
+
+use catalog::{Catalog, CATALOG_FILE, SYSTEM_CATALOG};
+use config::Config;
+use manifest::{Manifest, ManifestMount};
+use lock::{Lock, LockedSdk, LOCK_FILE};
+use origin::{Origin, Source};
+use project::{Project, SdkPin, PROJECT_FILE};
+use readonly::ReadOnlyDir;
+use verify::Verify;
+
+const SCRIPT_GUEST_DIR: &str = "/app";
+const CWD_GUEST_DIR: &str = ".";
+const STDLIB_GUEST_DIR: &str = "/lib";
+
+const EXIT_FAILURE: i32 = 1;
+const EXIT_MEMORY_LIMIT: i32 = 122;
+const EXIT_FUEL_EXHAUSTED: i32 = 123;
+const EXIT_TIMEOUT: i32 = 124;
+const EXIT_INSTALL_FAILED: i32 = 125;
+const EXIT_WASM_TRAP: i32 = 126;
+const EXIT_RUNTIME_MISSING: i32 = 127;
+
+// How long a guest may stay blocked in a host call past `--timeout` before
+// the process is stopped.
+const TIMEOUT_GRACE: Duration = Duration::from_millis(500);
 
 #[derive(Parser)]
 #[command(name = "rchidrun", version = "0.1.0", about = "Unified compiler for running scripts with WASM")]
 struct Cli {
+    #[arg(long, short = 'y', global = true, help = "Answer yes to prompts, installing missing runtimes automatically")]
+    yes: bool,
+    #[arg(long = "no-input", global = true, conflicts_with = "yes", help = "Never prompt; fail instead of asking for input")]
+    no_input: bool,
     #[command(subcommand)]
     command: Commands,
 }
@@ -20,145 +70,2474 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     #[command(about = "Run a script with a language")]
-    Run {
-        #[arg(help = "Programming language (e.g., python, javascript)")]
-        language: String,
-        #[arg(help = "Path to the script")]
-        script: String,
+    Run(Box<RunArgs>),
+    #[command(about = "Record the exact SDKs the project uses in rchidrun.lock")]
+    Lock,
+    #[command(about = "Manage installed SDKs")]
+    Sdk {
+        #[command(subcommand)]
+        command: SdkCommands,
     },
-    #[command(about = "List installed SDKs and supported languages")]
+    // Kept for scripts written against older releases.
+    #[command(hide = true, about = "Same as 'sdk list'")]
     SdkList,
+    #[command(external_subcommand)]
+    Script(Vec<String>),
 }
 
-fn sdk_dir() -> Result<PathBuf> {
-    let home = env::var("HOME").map_err(|_| anyhow!("$HOME not set"))?;
-    let mut dir = PathBuf::from(home);
-    dir.push(".rchidrun/plugins");
-    Ok(dir)
+#[derive(Subcommand)]
+enum SdkCommands {
+    #[command(about = "List installed SDKs and supported languages")]
+    List {
+        #[arg(long, help = "Print the list as JSON")]
+        json: bool,
+    },
+    #[command(about = "Show what an installed SDK's runtime is and where it came from")]
+    Info {
+        #[arg(value_name = "LANGUAGE[@VERSION]", help = "Language to inspect, e.g. python or python@3.11")]
+        language: String,
+    },
+    #[command(about = "Search the language catalog")]
+    Search {
+        #[arg(help = "Text to look for in names, aliases, extensions and descriptions; lists every language if omitted")]
+        term: Option<String>,
+    },
+    #[command(about = "Install an SDK without prompting")]
+    Install(InstallArgs),
+    #[command(about = "Write an installed SDK to a bundle that 'sdk import' can install on another machine")]
+    Export {
+        #[arg(value_name = "LANGUAGE[@VERSION]", help = "Language whose SDK to export, e.g. python or python@3.11")]
+        language: String,
+        #[arg(
+            long,
+            short = 'o',
+            value_name = "PATH",
+            help = "File to write (default: <language>-<version>.tar.gz in the current directory)"
+        )]
+        output: Option<PathBuf>,
+    },
+    #[command(about = "Install an SDK bundle written by 'sdk export'")]
+    Import {
+        #[arg(value_name = "BUNDLE", help = "The .tar.gz or .zip bundle to install")]
+        bundle: PathBuf,
+        #[arg(long, help = "Reinstall even if the SDK is already installed")]
+        force: bool,
+    },
+    #[command(about = "Remove an installed SDK, or one version of it")]
+    Remove {
+        #[arg(value_name = "LANGUAGE[@VERSION]", help = "Language whose SDK to remove, e.g. python or python@3.11")]
+        language: String,
+    },
+    #[command(about = "Re-fetch SDKs whose source has changed")]
+    Update {
+        #[arg(
+            value_name = "LANGUAGE[@VERSION]",
+            required_unless_present = "all",
+            help = "Language whose SDK to update, or a single version of it"
+        )]
+        language: Option<String>,
+        #[arg(long, conflicts_with = "language", help = "Update every installed SDK")]
+        all: bool,
+    },
+    #[command(about = "Set the version of a language used when none is requested")]
+    Default {
+        #[arg(help = "Language to change the default of")]
+        language: String,
+        #[arg(help = "Installed version to use, e.g. 3.12")]
+        version: String,
+    },
 }
 
-fn get_language_packages() -> HashMap<&'static str, &'static str> {
-    let mut map = HashMap::new();
-    map.insert("python", "wasmer/python");
-    map.insert("javascript", "wasmer/quickjs");
-    map.insert("ruby", "wasmer/ruby");
-    map
+#[derive(Args)]
+struct InstallArgs {
+    #[arg(
+        value_name = "LANGUAGE[@VERSION]",
+        required_unless_present = "locked",
+        help = "Language to install the SDK as (e.g., python or python@3.12)"
+    )]
+    language: Option<String>,
+    #[arg(long, value_name = "PKG", group = "source", help = "Wasmer package to install, e.g. wasmer/python@^3.12")]
+    wasmer: Option<String>,
+    #[arg(
+        long,
+        value_name = "URL",
+        group = "source",
+        help = "URL of a WASM runtime or SDK bundle to install (http, https or file)"
+    )]
+    url: Option<String>,
+    #[arg(long, value_name = "PATH", group = "source", help = "Local .wasm runtime, .webc package or SDK bundle to install")]
+    file: Option<PathBuf>,
+    #[arg(long, value_name = "HEX", value_parser = parse_sha256, help = "Refuse the download unless its SHA-256 matches")]
+    sha256: Option<String>,
+    #[arg(
+        long,
+        value_name = "URL|PATH",
+        help = "Detached minisign signature to verify the download against the trusted keys"
+    )]
+    signature: Option<String>,
+    #[arg(long, help = "Reinstall even if the SDK is already installed")]
+    force: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["source", "sha256", "signature"],
+        help = "Install exactly what rchidrun.lock records (every locked SDK unless a language is given)"
+    )]
+    locked: bool,
 }
 
-fn is_supported_language(language: &str) -> bool {
-    get_language_packages().contains_key(language)
+#[derive(Clone, Copy, PartialEq)]
+enum Prompt {
+    Ask,
+    Yes,
+    Never,
 }
 
-fn get_wasmer_package(language: &str) -> Option<&'static str> {
-    get_language_packages().get(language).copied()
+#[derive(Args, Default)]
+struct RunArgs {
+    #[arg(
+        value_name = "LANGUAGE|SCRIPT",
+        help = "Programming language (e.g., python, javascript, python@3.11), or the script itself to infer the language"
+    )]
+    target: String,
+    #[arg(long = "dir", value_name = "HOST[:GUEST]", help = "Preopen a host directory for the guest (repeatable)")]
+    dirs: Vec<String>,
+    #[arg(long = "dir-ro", value_name = "HOST[:GUEST]", help = "Preopen a host directory read-only (repeatable)")]
+    dirs_ro: Vec<String>,
+    #[arg(long, help = "Preopen the current directory as the guest's working directory")]
+    cwd: bool,
+    #[arg(long = "env", value_name = "KEY[=VALUE]", help = "Set an environment variable for the guest (repeatable)")]
+    envs: Vec<String>,
+    #[arg(long = "env-file", value_name = "PATH", help = "Read environment variables from a dotenv file (repeatable)")]
+    env_files: Vec<PathBuf>,
+    #[arg(long = "inherit-env", value_name = "PATTERN", help = "Pass host variables matching a pattern such as NODE_* (repeatable)")]
+    inherit_env: Vec<String>,
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, help = "Abort the script after a wall-clock time such as 30s or 5m")]
+    timeout: Option<Duration>,
+    #[arg(long = "max-memory", value_name = "SIZE", value_parser = parse_size, help = "Cap guest linear memory, e.g. 512M or 2G")]
+    max_memory: Option<usize>,
+    #[arg(long, value_name = "UNITS", help = "Abort the script after consuming this much fuel")]
+    fuel: Option<u64>,
+    #[arg(
+        trailing_var_arg = true,
+        allow_hyphen_values = true,
+        help = "Path to the script (when a language is given), followed by arguments passed to it"
+    )]
+    args: Vec<String>,
 }
 
-fn read_line() -> Result<String> {
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
-    Ok(input.trim().to_string())
+#[derive(Debug, Clone, Copy)]
+enum Failure {
+    RuntimeMissing,
+    InstallFailed,
+    WasmTrap,
+    Timeout,
+    MemoryLimit,
+    FuelExhausted,
+}
+
+impl Failure {
+    fn exit_code(self) -> i32 {
+        match self {
+            Failure::RuntimeMissing => EXIT_RUNTIME_MISSING,
+            Failure::InstallFailed => EXIT_INSTALL_FAILED,
+            Failure::WasmTrap => EXIT_WASM_TRAP,
+            Failure::Timeout => EXIT_TIMEOUT,
+            Failure::MemoryLimit => EXIT_MEMORY_LIMIT,
+            Failure::FuelExhausted => EXIT_FUEL_EXHAUSTED,
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::RuntimeMissing => write!(f, "Runtime not installed"),
+            Failure::InstallFailed => write!(f, "Installation failed"),
+            Failure::WasmTrap => write!(f, "WebAssembly trap"),
+            Failure::Timeout => write!(f, "Execution timed out"),
+            Failure::MemoryLimit => write!(f, "Memory limit exceeded"),
+            Failure::FuelExhausted => write!(f, "Fuel exhausted"),
+        }
+    }
+}
+
+struct Mount {
+    host: PathBuf,
+    guest: String,
+    read_only: bool,
+}
+
+struct Limits {
+    timeout: Option<Duration>,
+    max_memory: Option<usize>,
+    fuel: Option<u64>,
+}
+
+struct RunOptions {
+    mounts: Vec<Mount>,
+    cwd: bool,
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+    limits: Limits,
+    // A runtime and its digest, when it has just been checked against a
+    // lockfile.
+    locked_runtime: Option<(PathBuf, String)>,
+}
+
+struct MemoryLimiter {
+    max_memory: Option<usize>,
+    exceeded: bool,
+}
+
+impl ResourceLimiter for MemoryLimiter {
+    fn memory_growing(&mut self, _current: usize, desired: usize, _maximum: Option<usize>) -> Result<bool> {
+        match self.max_memory {
+            Some(max) if desired > max => {
+                self.exceeded = true;
+                Err(anyhow!("Guest requested {} bytes of memory, limit is {}", desired, max))
+            }
+            _ => Ok(true),
+        }
+    }
+
+    fn table_growing(&mut self, _current: u32, _desired: u32, _maximum: Option<u32>) -> Result<bool> {
+        Ok(true)
+    }
+}
+
+struct Host {
+    wasi: WasiCtx,
+    limiter: MemoryLimiter,
 }
 
-fn install_via_wasmer(language: &str) -> Result<()> {
-    let package = get_wasmer_package(language).ok_or(anyhow!("Language not supported"))?;
-    let mut sdk_path = sdk_dir()?;
-    sdk_path.push(language);
-    fs::create_dir_all(&sdk_path)?;
-    let status = Command::new("wasmer")
-        .args(["install", package, "--to", &sdk_path.to_string_lossy()])
-        .status()
-        .map_err(|e| anyhow!("Wasmer not found: {}. Please install Wasmer[](https://wasmer.io/).", e))?;
-    if status.success() {
-        println!("Installed '{}' via Wasmer", language);
-        Ok(())
+fn parse_duration(value: &str) -> std::result::Result<Duration, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format!("invalid duration '{}'", value))?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(format!("invalid duration unit '{}', expected ms, s, m or h", unit)),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("duration '{}' is too long", value))
+}
+
+fn parse_sha256(value: &str) -> std::result::Result<String, String> {
+    if value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(value.to_lowercase())
     } else {
-        Err(anyhow!("Wasmer installation failed"))
+        Err(format!("invalid SHA-256 '{}', expected 64 hex digits", value))
     }
 }
 
-fn install_via_url(language: &str, url: &str) -> Result<()> {
-    let mut sdk_path = sdk_dir()?;
-    sdk_path.push(language);
-    fs::create_dir_all(&sdk_path)?;
-    sdk_path.push("runtime.wasm");
-    let mut file = File::create(&sdk_path)?;
-    let mut resp = get(url).map_err(|e| anyhow!("Failed to download: {}", e))?;
-    copy(&mut resp, &mut file)?;
-    println!("Installed '{}' from URL", language);
-    Ok(())
+fn parse_size(value: &str) -> std::result::Result<usize, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: usize = number.parse().map_err(|e: std::num::ParseIntError| match e.kind() {
+        std::num::IntErrorKind::PosOverflow => format!("size '{}' is too large", value),
+        _ => format!("invalid size '{}'", value),
+    })?;
+    let multiplier = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        _ => return Err(format!("invalid size unit '{}', expected K, M or G", unit)),
+    };
+    number
+        .checked_mul(multiplier)
+        .ok_or(format!("size '{}' is too large", value))
 }
 
-fn run_sdk(language: &str, script: &str) -> Result<()> {
-    let mut wasm_path = sdk_dir()?;
-    wasm_path.push(language);
-    wasm_path.push("runtime.wasm");
-    let engine = Engine::default();
-    let module = Module::from_file(&engine, &wasm_path)?;
-    let wasi = WasiCtxBuilder::new()
-        .inherit_stdio()
-        .args(&[script])?
-        .build();
-    let mut store = Store::new(&engine, wasi);
-    let mut linker = Linker::new(&engine);
-    wasmtime_wasi::add_to_linker(&mut linker, |ctx: &mut _| ctx.clone())?;
-    let instance = linker.instantiate(&mut store, &module)?;
-    let start = instance
-        .get_func(&mut store, "_start")
-        .ok_or(anyhow!("_start function not found"))?;
-    start.call(&mut store, &[], &mut [])?;
-    Ok(())
+fn parse_mount(spec: &str, read_only: bool) -> Result<Mount> {
+    let (host, guest) = match spec.split_once(':') {
+        Some((host, guest)) if !host.is_empty() && !guest.is_empty() => (host, guest),
+        None if !spec.is_empty() => (spec, spec),
+        _ => return Err(anyhow!("Invalid directory mapping '{}', expected HOST[:GUEST]", spec)),
+    };
+    Ok(Mount {
+        host: PathBuf::from(host),
+        guest: guest.to_string(),
+        read_only,
+    })
+}
+
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => {
+            let Some(name) = name.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=name.len())
+                .filter(|&i| name.is_char_boundary(i))
+                .any(|i| matches_pattern(rest, &name[i..]))
+        }
+    }
+}
+
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    // A quoted value may be followed by a comment, but nothing else.
+    let closed = |rest: &str| rest.trim().is_empty() || rest.trim_start().starts_with('#');
+    if let Some((inner, rest)) = value.strip_prefix('\'').and_then(|v| v.split_once('\'')) {
+        if closed(rest) {
+            return inner.to_string();
+        }
+    }
+    if let Some(quoted) = value.strip_prefix('"') {
+        let mut out = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' if closed(&quoted[i + 1..]) => return out,
+                '"' => break,
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => out.push('\n'),
+                    Some((_, 't')) => out.push('\t'),
+                    Some((_, other)) => out.push(other),
+                    None => break,
+                },
+                _ => out.push(c),
+            }
+        }
+    }
+    match value.find(" #") {
+        Some(i) => value[..i].trim_end().to_string(),
+        None => value.to_string(),
+    }
+}
+
+fn parse_env_file(path: &Path) -> Result<Vec<(String, String)>> {
+    let content =
+        fs::read_to_string(path).map_err(|e| anyhow!("Cannot read env file '{}': {}", path.display(), e))?;
+    let mut vars = Vec::new();
+    for (n, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .filter(|(key, _)| !key.trim().is_empty())
+            .ok_or(anyhow!("{}:{}: expected KEY=VALUE", path.display(), n + 1))?;
+        vars.push((key.trim().to_string(), unquote(value)));
+    }
+    Ok(vars)
+}
+
+fn build_env(envs: &[String], env_files: &[PathBuf], inherit: &[String]) -> Result<Vec<(String, String)>> {
+    let mut vars = BTreeMap::new();
+    for (key, value) in env::vars() {
+        if inherit.iter().any(|p| matches_pattern(p, &key)) {
+            vars.insert(key, value);
+        }
+    }
+    for path in env_files {
+        vars.extend(parse_env_file(path)?);
+    }
+    for spec in envs {
+        match spec.split_once('=') {
+            Some((key, value)) => {
+                vars.insert(key.to_string(), value.to_string());
+            }
+            None => {
+                if let Ok(value) = env::var(spec) {
+                    vars.insert(spec.clone(), value);
+                }
+            }
+        }
+    }
+    Ok(vars.into_iter().collect())
 }
 
-fn run_language(language: &str, script: &str) -> Result<()> {
-    let sdk_path = sdk_dir()?.join(language).join("runtime.wasm");
-    if sdk_path.exists() {
-        run_sdk(language, script)
+// SDKs without an `sdk.toml` run with the argv template, environment and
+// mounts from their catalog entry; mounts whose directory is missing are
+// skipped, along with the environment that refers to them.
+fn builtin_manifest(language: &str, sdk_path: &Path) -> Manifest {
+    let entry = catalog().get(language).cloned().unwrap_or_else(|| catalog::Entry {
+        mounts: vec![ManifestMount {
+            host: "lib".to_string(),
+            guest: STDLIB_GUEST_DIR.to_string(),
+            writable: false,
+        }],
+        ..catalog::Entry::default()
+    });
+    let args = if entry.args.is_empty() {
+        vec![language.to_string(), "{script}".to_string()]
     } else {
-        println!("No runtime found for '{}'.", language);
-        if is_supported_language(language) {
-            print!("Install it via Wasmer? (y/n): ");
-            io::stdout().flush()?;
-            let choice = read_line()?;
-            if choice.to_lowercase() == "y" {
-                install_via_wasmer(language)?;
-                run_sdk(language, script)
-            } else {
-                Err(anyhow!("Installation aborted"))
+        entry.args
+    };
+    let mounts: Vec<ManifestMount> = entry.mounts.into_iter().filter(|m| sdk_path.join(&m.host).is_dir()).collect();
+    let mut manifest = Manifest {
+        args,
+        ..Manifest::default()
+    };
+    if !mounts.is_empty() {
+        manifest.env = entry.env;
+        manifest.mounts = mounts;
+    }
+    manifest
+}
+
+fn load_manifest(language: &str, sdk_path: &Path) -> Result<Manifest> {
+    match Manifest::load(sdk_path)? {
+        Some(mut manifest) => {
+            if manifest.args.is_empty() {
+                manifest.args = builtin_manifest(language, sdk_path).args;
             }
-        } else {
-            print!("Language not predefined. Provide a URL to the WASM runtime: ");
-            io::stdout().flush()?;
-            let url = read_line()?;
-            install_via_url(language, &url)?;
-            run_sdk(language, script)
+            Ok(manifest)
         }
+        None => Ok(builtin_manifest(language, sdk_path)),
     }
 }
 
-fn sdk_list() -> Result<()> {
-    let dir = sdk_dir()?;
-    println!("Installed SDKs:");
-    if let Ok(entries) = fs::read_dir(&dir) {
+fn runtime_path(language: &str, sdk_path: &Path) -> Result<PathBuf> {
+    Ok(load_manifest(language, sdk_path)?.wasm_path(sdk_path))
+}
+
+fn installed_sdk(language: &str, version: Option<&str>) -> Result<Option<PathBuf>> {
+    for root in plugin_roots()? {
+        let lang_dir = root.join(language);
+        if let Some(version) = layout::resolve(&lang_dir, version) {
+            return Ok(Some(lang_dir.join(version)));
+        }
+    }
+    Ok(None)
+}
+
+fn preopen(wasi: &WasiCtx, host: &Path, guest: &str, read_only: bool) -> Result<()> {
+    let dir = Dir::open_ambient_dir(host, ambient_authority())
+        .map_err(|e| anyhow!("Failed to open directory '{}': {}", host.display(), e))?;
+    let dir = Box::new(wasmtime_wasi::dir::Dir::from_cap_std(dir));
+    if read_only {
+        wasi.push_preopened_dir(Box::new(ReadOnlyDir(dir)), guest)?;
+    } else {
+        wasi.push_preopened_dir(dir, guest)?;
+    }
+    Ok(())
+}
+
+fn env_dir(name: &str) -> Option<PathBuf> {
+    env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from)
+}
+
+// XDG base directories are only honoured when set to an absolute path.
+fn xdg_dir(name: &str) -> Option<PathBuf> {
+    env_dir(name).filter(|dir| dir.is_absolute()).map(|dir| dir.join("rchidrun"))
+}
+
+// Holds the config file, trusted keys and user catalog.
+fn rchidrun_home() -> Result<PathBuf> {
+    env_dir("RCHIDRUN_HOME")
+        .or_else(|| env_dir("HOME").map(|home| home.join(".rchidrun")))
+        .or_else(|| xdg_dir("XDG_CONFIG_HOME"))
+        .ok_or(anyhow!("Cannot find the rchidrun home; set RCHIDRUN_HOME or HOME"))
+}
+
+// Holds installed SDKs. Existing installs under the home stay where they
+// are; otherwise $XDG_DATA_HOME is used when it is set.
+fn data_dir() -> Result<PathBuf> {
+    let home = rchidrun_home()?;
+    if env_dir("RCHIDRUN_HOME").is_some() || home.join("plugins").is_dir() {
+        return Ok(home);
+    }
+    Ok(xdg_dir("XDG_DATA_HOME").unwrap_or(home))
+}
+
+// Holds partial downloads, the catalog index and compiled modules for SDKs
+// in read-only plugin roots; everything in it can be deleted.
+fn cache_dir() -> Result<PathBuf> {
+    if let Some(home) = env_dir("RCHIDRUN_HOME") {
+        return Ok(home.join("cache"));
+    }
+    match xdg_dir("XDG_CACHE_HOME") {
+        Some(dir) => Ok(dir),
+        None => Ok(data_dir()?.join("cache")),
+    }
+}
+
+fn modules_cache_dir() -> Result<PathBuf> {
+    Ok(cache_dir()?.join("modules"))
+}
+
+// The plugin root SDKs are installed into.
+fn sdk_dir() -> Result<PathBuf> {
+    Ok(data_dir()?.join("plugins"))
+}
+
+// Every plugin root in lookup order: the user's own, then the directories
+// listed in RCHIDRUN_PATH, which are only read from.
+fn plugin_roots() -> Result<Vec<PathBuf>> {
+    let mut roots = vec![sdk_dir()?];
+    if let Some(path) = env::var_os("RCHIDRUN_PATH") {
+        for root in env::split_paths(&path) {
+            if !root.as_os_str().is_empty() && !roots.contains(&root) {
+                roots.push(root);
+            }
+        }
+    }
+    Ok(roots)
+}
+
+fn config_path() -> Result<PathBuf> {
+    Ok(rchidrun_home()?.join("config.toml"))
+}
+
+fn registry_endpoint(config: &Config) -> String {
+    env::var("RCHIDRUN_REGISTRY")
+        .ok()
+        .or_else(|| config.registry.clone())
+        .unwrap_or_else(|| registry::DEFAULT_REGISTRY.to_string())
+}
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+fn catalog() -> &'static Catalog {
+    CATALOG.get_or_init(|| Catalog::new().unwrap_or_default())
+}
+
+// Reads the catalog index from a path, or from a URL fetched at most once a
+// day; a stale copy is used when the URL cannot be reached.
+fn catalog_index(location: &str) -> Result<String> {
+    if !location.starts_with("http://") && !location.starts_with("https://") {
+        let path = download::file_url_path(location).unwrap_or(Path::new(location));
+        return fs::read_to_string(path).map_err(|e| anyhow!("Cannot read catalog '{}': {}", path.display(), e));
+    }
+    let cached = cache_dir()?.join("catalog-index.toml");
+    let fresh = fs::metadata(&cached)
+        .and_then(|m| m.modified())
+        .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age < Duration::from_secs(24 * 3600)));
+    if fresh {
+        return Ok(fs::read_to_string(&cached)?);
+    }
+    // Every command loads the catalog, so a failed fetch is not retried.
+    let fetched = downloader().and_then(|mut d| {
+        d.settings.retries = 0;
+        let download = d
+            .fetch(location, None)?
+            .ok_or(anyhow!("Failed to download {}: unexpected 304 response", location))?;
+        Ok(fs::read_to_string(&download.path)?)
+    });
+    match fetched {
+        Ok(content) => {
+            fs::create_dir_all(cache_dir()?)?;
+            fs::write(&cached, &content)?;
+            Ok(content)
+        }
+        Err(e) if cached.is_file() => {
+            eprintln!("Warning: using the cached catalog index: {:#}", e);
+            Ok(fs::read_to_string(&cached)?)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn load_catalog(config: &Config) -> Result<Catalog> {
+    let mut catalog = Catalog::new()?;
+    if let Some(index) = &config.catalog {
+        // Installed SDKs must keep working without the index, e.g. offline.
+        match catalog_index(index) {
+            Ok(content) => catalog.merge(&content, index)?,
+            Err(e) => eprintln!("Warning: ignoring the catalog index '{}': {:#}", index, e),
+        }
+    }
+    catalog.merge_file(Path::new(SYSTEM_CATALOG))?;
+    catalog.merge_file(&rchidrun_home()?.join(CATALOG_FILE))?;
+    Ok(catalog)
+}
+
+// Maps a language as typed to the name its SDK is installed under: aliases
+// from the config or the built-in table, and supported or installed names, all
+// matched regardless of case. Unknown names are returned unchanged.
+fn canonical_language(name: &str, config: &Config) -> String {
+    canonical_language_among(name, config, &installed_languages())
+}
+
+fn canonical_language_among(name: &str, config: &Config, installed: &[String]) -> String {
+    let lower = name.to_lowercase();
+    if let Some(language) = config.alias(&lower) {
+        return alias_target(language);
+    }
+    if let Some(language) = catalog().alias(&lower) {
+        return language.to_string();
+    }
+    if is_supported_language(&lower) {
+        return lower;
+    }
+    installed
+        .iter()
+        .find(|language| language.eq_ignore_ascii_case(name))
+        .map_or_else(|| name.to_string(), String::clone)
+}
+
+// A configured alias may point at another alias or use any case
+// (`py = "Python"`), so its target goes through the catalog's aliases once.
+fn alias_target(target: &str) -> String {
+    let language = target.to_lowercase();
+    catalog().alias(&language).map_or(language, str::to_string)
+}
+
+fn canonical_target(target: &str, config: &Config) -> Result<String> {
+    Ok(match split_version(target)? {
+        (language, Some(version)) => format!("{}@{}", canonical_language(language, config), version),
+        (language, None) => canonical_language(language, config),
+    })
+}
+
+fn aliases_of(language: &str, config: &Config) -> Vec<String> {
+    let configured = config.aliases.iter().filter(|(_, l)| alias_target(l) == language).map(|(a, _)| a);
+    let mut aliases: Vec<String> = catalog()
+        .get(language)
+        .map_or(&[][..], |entry| &entry.aliases)
+        .iter()
+        .chain(configured)
+        .map(|a| a.to_lowercase())
+        .collect();
+    aliases.sort();
+    aliases.dedup();
+    aliases
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+        }
+    }
+    row[b.len()]
+}
+
+// The closest known language or alias to a name that matched nothing, for
+// "did you mean" hints.
+fn suggest_language(name: &str, config: &Config) -> Option<String> {
+    suggest_language_among(name, config, &installed_languages())
+}
+
+fn suggest_language_among(name: &str, config: &Config, installed: &[String]) -> Option<String> {
+    let name = name.to_lowercase();
+    let limit = (name.chars().count() / 3).max(1);
+    let catalog = catalog();
+    let candidates = catalog
+        .languages
+        .iter()
+        .flat_map(|(language, entry)| std::iter::once(language).chain(&entry.aliases))
+        .cloned()
+        .chain(config.aliases.keys().map(|a| a.to_lowercase()))
+        .chain(installed.iter().cloned());
+    candidates
+        .map(|candidate| (edit_distance(&name, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min()
+        .map(|(_, candidate)| canonical_language_among(&candidate, config, installed))
+}
+
+fn did_you_mean(name: &str, config: &Config) -> String {
+    match suggest_language(name, config) {
+        Some(language) => format!(" Did you mean '{}'?", language),
+        None => String::new(),
+    }
+}
+
+fn is_installed_language(language: &str) -> bool {
+    installed_sdk(language, None)
+        .ok()
+        .flatten()
+        .is_some_and(|sdk_path| runtime_path(language, &sdk_path).is_ok_and(|path| path.exists()))
+}
+
+fn languages_in(root: &Path) -> Vec<String> {
+    let mut languages = Vec::new();
+    if let Ok(entries) = fs::read_dir(root) {
         for entry in entries.flatten() {
             if entry.path().is_dir() {
-                if let Some(n) = entry.file_name().to_str() {
-                    println!("- {}", n);
+                if let Some(n) = entry.file_name().to_str().filter(|n| !n.starts_with('.')) {
+                    languages.push(n.to_string());
                 }
             }
         }
     }
-    println!("\nSupported languages (via Wasmer):");
-    for (lang, pkg) in get_language_packages() {
-        println!("- {} ({})", lang, pkg);
+    languages.sort();
+    languages
+}
+
+fn installed_languages() -> Vec<String> {
+    let mut languages: Vec<String> = plugin_roots()
+        .unwrap_or_default()
+        .iter()
+        .flat_map(|root| languages_in(root))
+        .collect();
+    languages.sort();
+    languages.dedup();
+    languages
+}
+
+fn looks_like_language(target: &str, config: &Config) -> bool {
+    let Ok((target, _)) = split_version(target) else {
+        return false;
+    };
+    let target = &canonical_language(target, config);
+    is_supported_language(target)
+        || is_installed_language(target)
+        || (!Path::new(target).exists() && !target.contains(['.', '/', '\\']))
+}
+
+fn installed_language_for_extension(extension: &str) -> Option<String> {
+    installed_languages().into_iter().find(|lang| {
+        let sdk_path = installed_sdk(lang, None).ok().flatten();
+        sdk_path.and_then(|p| Manifest::load(&p).ok().flatten()).is_some_and(|m| {
+            m.extensions
+                .iter()
+                .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(extension))
+        })
+    })
+}
+
+fn language_from_interpreter(interpreter: &str, config: &Config) -> Option<String> {
+    let name = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let language = canonical_language(name, config);
+    if is_supported_language(&language) || is_installed_language(&language) {
+        return Some(language);
+    }
+    catalog().interpreter_language(name).map(str::to_string)
+}
+
+fn shebang_interpreter(script: &Path) -> Option<String> {
+    let mut line = String::new();
+    BufReader::new(File::open(script).ok()?).take(512).read_line(&mut line).ok()?;
+    let line = line.trim_end().strip_prefix("#!")?;
+    let mut words = line.split_whitespace();
+    let program = Path::new(words.next()?).file_name()?.to_str()?.to_string();
+    if program == "env" {
+        words.find(|w| !w.starts_with('-') && !w.contains('=')).map(str::to_string)
+    } else {
+        Some(program)
+    }
+}
+
+fn detect_language(script: &str, config: &Config) -> Result<String> {
+    let path = Path::new(script);
+    let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+    if let Some(language) = extension.as_deref().and_then(|e| config.extension_language(e)) {
+        return Ok(language.to_string());
+    }
+    let by_extension = extension.as_deref().and_then(|e| {
+        installed_language_for_extension(e)
+            .or_else(|| catalog().extension_language(e).map(str::to_string))
+            .or_else(|| is_installed_language(e).then(|| e.to_string()))
+    });
+    let by_shebang = shebang_interpreter(path).and_then(|i| language_from_interpreter(&i, config));
+    match (by_extension, by_shebang) {
+        (Some(a), Some(b)) if a != b => Err(anyhow!(
+            "Cannot infer the language of '{}': its extension suggests '{}' but its shebang suggests '{}'. \
+             Pass it explicitly: rchidrun run <language> {}",
+            script,
+            a,
+            b,
+            script
+        )),
+        (Some(language), _) | (None, Some(language)) => Ok(language),
+        (None, None) => {
+            let known: Vec<String> = catalog()
+                .languages
+                .iter()
+                .filter(|(_, entry)| !entry.extensions.is_empty())
+                .map(|(lang, entry)| {
+                    let exts: Vec<&str> = entry.extensions.iter().map(|e| e.trim_start_matches('.')).collect();
+                    format!("  {} (.{})", lang, exts.join(", ."))
+                })
+                .collect();
+            Err(anyhow!(
+                "Cannot infer the language of '{}'. Pass it explicitly: rchidrun run <language> {}\n\
+                 Known languages:\n{}",
+                script,
+                script,
+                known.join("\n")
+            ))
+        }
+    }
+}
+
+fn is_supported_language(language: &str) -> bool {
+    catalog().get(language).is_some_and(catalog::Entry::has_source)
+}
+
+fn read_line() -> Result<String> {
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().to_string())
+}
+
+// Installs are built in a hidden staging directory next to their final
+// location and renamed into place, so an interrupted install never leaves a
+// half-written SDK behind.
+fn install_artifact(
+    language: &str,
+    version: &str,
+    artifact: &Path,
+    source: Source,
+    container: bool,
+    verify: &Verify,
+) -> Result<PathBuf> {
+    layout::check_name("language", language)?;
+    layout::check_name("version", version)?;
+    let digest = download::sha256_file(artifact)?;
+    if let Some(expected) = &verify.sha256 {
+        verify::check_sha256(&digest, expected).with_context(|| format!("Cannot install '{}@{}'", language, version))?;
+    }
+    if let Some(location) = &verify.signature {
+        let config = Config::load(&config_path()?)?;
+        let keys_path = match config.trusted_keys {
+            Some(path) => path,
+            None => rchidrun_home()?.join("trusted-keys"),
+        };
+        let keys = verify::load_trusted_keys(&keys_path)?;
+        let signature = String::from_utf8(fetch_location(location)?)
+            .map_err(|_| anyhow!("Signature '{}' is not a minisign signature", location))?;
+        verify::check_signature(&fs::read(artifact)?, &signature, &keys)
+            .with_context(|| format!("Cannot install '{}@{}' (signature {})", language, version, location))?;
+    }
+    let mut origin = Origin::new(source, digest);
+    origin.signature = verify.signature.clone();
+    let lang_dir = sdk_dir()?.join(language);
+    fs::create_dir_all(&lang_dir)?;
+    layout::remove_stale(&lang_dir);
+    let staging = tempfile::Builder::new().prefix(".staging-").tempdir_in(&lang_dir)?;
+    if container {
+        let container = staging.path().join(package::CONTAINER_FILE);
+        fs::copy(artifact, &container)?;
+        package::unpack(&container, staging.path(), Some(version.to_string()))?;
+    } else if let Some(format) = bundle::format(artifact)? {
+        bundle::unpack(artifact, format, staging.path())?;
+    } else {
+        fs::copy(artifact, staging.path().join("runtime.wasm"))?;
+    }
+    validate_runtime(language, staging.path()).with_context(|| format!("Cannot install '{}@{}'", language, version))?;
+    origin.files = layout::digests(staging.path())?;
+    origin.save(staging.path())?;
+    let sdk_path = lang_dir.join(version);
+    layout::replace(staging.path(), &sdk_path)?;
+    if !layout::has_default(&lang_dir) {
+        layout::set_default(&lang_dir, version)?;
+    }
+    Ok(sdk_path)
+}
+
+// Serializes changes to the plugin directory across rchidrun processes. The
+// lock is released when the returned file is dropped.
+fn lock_plugins() -> Result<File> {
+    let dir = sdk_dir()?;
+    fs::create_dir_all(&dir)?;
+    let file = File::options()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(dir.join(".lock"))?;
+    if file.try_lock_exclusive().is_err() {
+        eprintln!("Waiting for another rchidrun process to finish installing...");
+        file.lock_exclusive()?;
+    }
+    Ok(file)
+}
+
+fn downloader() -> Result<download::Downloader> {
+    let path = config_path()?;
+    let config = Config::load(&path)?.download;
+    let mut settings = download::Settings::default();
+    let invalid = |key: &str, e: String| anyhow!("Invalid download.{} in '{}': {}", key, path.display(), e);
+    if let Some(retries) = config.retries {
+        settings.retries = retries;
+    }
+    if let Some(timeout) = &config.timeout {
+        settings.timeout = parse_duration(timeout).map_err(|e| invalid("timeout", e))?;
+    }
+    if let Some(timeout) = &config.connect_timeout {
+        settings.connect_timeout = parse_duration(timeout).map_err(|e| invalid("connect-timeout", e))?;
+    }
+    if let Some(size) = &config.max_size {
+        settings.max_size = parse_size(size).map_err(|e| invalid("max-size", e))? as u64;
+    }
+    Ok(download::Downloader {
+        dir: cache_dir()?.join("downloads"),
+        settings,
+    })
+}
+
+fn prepare_install(language: &str, version: &str, force: bool) -> Result<()> {
+    if !force && sdk_dir()?.join(language).join(version).exists() {
+        return Err(anyhow!(
+            "SDK '{}@{}' is already installed; use --force to reinstall",
+            language,
+            version
+        ));
     }
     Ok(())
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
-    match cli.command {
-        Commands::Run { language, script } => run_language(&language, &script)?,
-        Commands::SdkList => sdk_list()?,
+fn install_release(
+    language: &str,
+    release: &registry::Release,
+    constraint: Option<String>,
+    verify: &Verify,
+) -> Result<PathBuf> {
+    let download = registry::download(release, &downloader()?)?;
+    let source = Source::Wasmer {
+        package: release.name.clone(),
+        constraint,
+        version: release.version.clone(),
+    };
+    install_artifact(language, &release.version, &download.path, source, true, verify)
+}
+
+fn install_via_wasmer(language: &str, package: &str, force: bool, verify: &Verify) -> Result<PathBuf> {
+    let config = Config::load(&config_path()?)?;
+    let release = registry::resolve(&registry_endpoint(&config), package, &downloader()?)?;
+    prepare_install(language, &release.version, force)?;
+    let constraint = package.split_once('@').map(|(_, c)| c.to_string());
+    let sdk_path = install_release(language, &release, constraint, verify)?;
+    println!("Installed '{}@{}' via Wasmer ({})", language, release.version, release.name);
+    Ok(sdk_path)
+}
+
+fn fetch_location(location: &str) -> Result<Vec<u8>> {
+    if location.starts_with("http://") || location.starts_with("https://") {
+        let download = downloader()?
+            .fetch(location, None)?
+            .ok_or(anyhow!("Failed to download {}: unexpected 304 response", location))?;
+        Ok(fs::read(&download.path)?)
+    } else {
+        let path = download::file_url_path(location).unwrap_or(Path::new(location));
+        fs::read(path).map_err(|e| anyhow!("Cannot read '{}': {}", path.display(), e))
     }
+}
+
+fn install_via_url(language: &str, version: &str, url: &str, verify: &Verify) -> Result<PathBuf> {
+    let download = downloader()?
+        .fetch(url, None)?
+        .ok_or(anyhow!("Failed to download {}: unexpected 304 response", url))?;
+    let source = Source::Url {
+        url: url.to_string(),
+        etag: download.etag.clone(),
+    };
+    let sdk_path = install_artifact(language, version, &download.path, source, false, verify)?;
+    println!("Installed '{}@{}' from URL", language, version);
+    Ok(sdk_path)
+}
+
+fn is_container_path(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "webc")
+}
+
+fn install_from_file(language: &str, version: &str, path: &Path, verify: &Verify) -> Result<PathBuf> {
+    let canonical = fs::canonicalize(path).map_err(|e| anyhow!("Cannot read '{}': {}", path.display(), e))?;
+    let source = Source::File {
+        path: canonical.display().to_string(),
+    };
+    let sdk_path = install_artifact(language, version, &canonical, source, is_container_path(path), verify)?;
+    println!("Installed '{}@{}' from {}", language, version, path.display());
+    Ok(sdk_path)
+}
+
+fn split_version(target: &str) -> Result<(&str, Option<&str>)> {
+    let (language, version) = match target.split_once('@') {
+        Some((language, version)) => (language, Some(version)),
+        None => (target, None),
+    };
+    layout::check_name("language", language)?;
+    if let Some(version) = version {
+        layout::check_name("version", version)?;
+    }
+    Ok((language, version))
+}
+
+fn wasmer_spec(package: &str, requested: Option<&str>) -> String {
+    match requested {
+        Some(version) if !package.contains('@') => format!("{}@{}", package, layout::constraint(version)),
+        _ => package.to_string(),
+    }
+}
+
+fn sdk_install(args: &InstallArgs) -> Result<()> {
+    if args.locked {
+        return sdk_install_locked(args.language.as_deref(), args.force);
+    }
+    let target = args.language.as_deref().ok_or(anyhow!("No language given"))?;
+    let (language, requested) = split_version(target)?;
+    let signature = match &args.signature {
+        Some(signature) if !signature.contains("://") => Some(
+            fs::canonicalize(signature)
+                .map_err(|e| anyhow!("Cannot read '{}': {}", signature, e))?
+                .display()
+                .to_string(),
+        ),
+        signature => signature.clone(),
+    };
+    let verify = Verify {
+        sha256: args.sha256.clone(),
+        signature,
+    };
+    let version = requested.unwrap_or(layout::UNVERSIONED);
+    if let Some(url) = &args.url {
+        prepare_install(language, version, args.force)?;
+        install_via_url(language, version, url, &verify)?;
+    } else if let Some(path) = &args.file {
+        prepare_install(language, version, args.force)?;
+        install_from_file(language, version, path, &verify)?;
+    } else {
+        let entry = catalog().get(language);
+        if let Some(package) = args.wasmer.as_deref().or(entry.and_then(|e| e.wasmer.as_deref())) {
+            install_via_wasmer(language, &wasmer_spec(package, requested), args.force, &verify)?;
+        } else if let Some((url, entry)) = entry.and_then(|e| e.url.as_deref().map(|url| (url, e))) {
+            let verify = Verify {
+                sha256: verify.sha256.clone().or(entry.sha256.clone()),
+                ..verify
+            };
+            prepare_install(language, version, args.force)?;
+            install_via_url(language, version, url, &verify)?;
+        } else {
+            let config = Config::load(&config_path()?)?;
+            return Err(anyhow!(
+                "No package known for '{}'; pass --wasmer, --url or --file.{}",
+                language,
+                did_you_mean(language, &config)
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn find_version(language: &str, requested: &str) -> Result<String> {
+    let lang_dir = sdk_dir()?.join(language);
+    layout::resolve(&lang_dir, Some(requested)).ok_or_else(|| {
+        let installed = layout::versions(&lang_dir);
+        if installed.is_empty() {
+            anyhow!("SDK '{}' is not installed", language)
+        } else {
+            anyhow!(
+                "No installed version of '{}' matches '{}' (installed: {})",
+                language,
+                requested,
+                installed.join(", ")
+            )
+        }
+    })
+}
+
+fn sdk_export(target: &str, output: Option<&Path>) -> Result<()> {
+    let (language, requested) = split_version(target)?;
+    let sdk_path = match (installed_sdk(language, requested)?, requested) {
+        (Some(sdk_path), _) => sdk_path,
+        (None, Some(requested)) => sdk_dir()?.join(language).join(find_version(language, requested)?),
+        (None, None) => return Err(anyhow!("SDK '{}' is not installed", language)),
+    };
+    let version = sdk_path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+    if !runtime_path(language, &sdk_path)?.is_file() {
+        return Err(anyhow!(
+            "SDK '{}@{}' has no runtime; reinstall it with 'rchidrun sdk install {}@{} --force'",
+            language,
+            version,
+            language,
+            version
+        ));
+    }
+    let output = match output {
+        Some(output) => output.to_path_buf(),
+        None => PathBuf::from(format!("{}-{}.tar.gz", language, version)),
+    };
+    bundle::export(&sdk_path, language, &version, &output)
+        .with_context(|| format!("Cannot write '{}'", output.display()))?;
+    println!("Exported '{}@{}' to {}", language, version, output.display());
+    Ok(())
+}
+
+fn sdk_import(path: &Path, force: bool) -> Result<()> {
+    fs::metadata(path).map_err(|e| anyhow!("Cannot read '{}': {}", path.display(), e))?;
+    let (language, version) = bundle::identify(path)?.ok_or(anyhow!(
+        "'{}' is not a bundle written by 'rchidrun sdk export'; install it with 'rchidrun sdk install LANGUAGE[@VERSION] --file {}'",
+        path.display(),
+        path.display()
+    ))?;
+    prepare_install(&language, &version, force)?;
+    install_from_file(&language, &version, path, &Verify::default())?;
     Ok(())
-}
\ No newline at end of file
+}
+
+fn sdk_remove(target: &str) -> Result<()> {
+    let (language, requested) = split_version(target)?;
+    let lang_dir = sdk_dir()?.join(language);
+    if !lang_dir.is_dir() {
+        return Err(anyhow!("SDK '{}' is not installed", language));
+    }
+    let version = match requested {
+        Some(requested) => find_version(language, requested)?,
+        None => {
+            fs::remove_dir_all(&lang_dir)?;
+            println!("Removed '{}'", language);
+            return Ok(());
+        }
+    };
+    fs::remove_dir_all(lang_dir.join(&version))?;
+    if layout::versions(&lang_dir).is_empty() {
+        fs::remove_dir_all(&lang_dir)?;
+    } else if !layout::has_default(&lang_dir) {
+        let _ = fs::remove_file(lang_dir.join(layout::DEFAULT_FILE));
+    }
+    println!("Removed '{}@{}'", language, version);
+    Ok(())
+}
+
+fn sdk_default(language: &str, requested: &str) -> Result<()> {
+    layout::check_name("language", language)?;
+    let version = find_version(language, requested)?;
+    layout::set_default(&sdk_dir()?.join(language), &version)?;
+    println!("Default version of '{}' is now {}", language, version);
+    Ok(())
+}
+
+fn update_version(language: &str, version: &str, endpoint: &str) -> Result<()> {
+    let lang_dir = sdk_dir()?.join(language);
+    let sdk_path = lang_dir.join(version);
+    let mut origin = Origin::load(&sdk_path)?.ok_or(anyhow!(
+        "SDK '{}@{}' has no origin record; reinstall it with 'rchidrun sdk install {}@{} --force'",
+        language,
+        version,
+        language,
+        version
+    ))?;
+    let previous = origin.source.to_string();
+    let reverify = Verify {
+        sha256: None,
+        signature: origin.signature.clone(),
+    };
+    let updated = match &origin.source {
+        Source::Wasmer {
+            package,
+            constraint,
+            version: installed,
+        } => {
+            let spec = match constraint {
+                Some(constraint) => format!("{}@{}", package, constraint),
+                None => package.clone(),
+            };
+            let release = registry::resolve(endpoint, &spec, &downloader()?)?;
+            if &release.version == installed {
+                None
+            } else {
+                if !lang_dir.join(&release.version).exists() {
+                    install_release(language, &release, constraint.clone(), &reverify)?;
+                }
+                if layout::default_version(&lang_dir).as_deref() == Some(version) {
+                    layout::set_default(&lang_dir, &release.version)?;
+                }
+                if release.version != version {
+                    fs::remove_dir_all(&sdk_path)?;
+                }
+                Some(format!("{}@{}", release.name, release.version))
+            }
+        }
+        Source::Url { url, etag } => match downloader()?.fetch(url, etag.as_deref())? {
+            None => None,
+            Some(download) => {
+                let source = Source::Url {
+                    url: url.clone(),
+                    etag: download.etag.clone(),
+                };
+                if download::sha256_file(&download.path)? == origin.sha256 {
+                    origin.source = source;
+                    origin.save(&sdk_path)?;
+                    None
+                } else {
+                    install_artifact(language, version, &download.path, source, false, &reverify)?;
+                    Some(url.clone())
+                }
+            }
+        },
+        Source::File { path } => {
+            let digest = download::sha256_file(Path::new(path)).map_err(|e| anyhow!("Cannot read '{}': {}", path, e))?;
+            if digest == origin.sha256 {
+                None
+            } else {
+                let container = is_container_path(Path::new(path));
+                install_artifact(language, version, Path::new(path), origin.source.clone(), container, &reverify)?;
+                Some(path.clone())
+            }
+        }
+    };
+    match updated {
+        Some(current) => println!("Updated '{}@{}' ({})", language, version, current),
+        None => println!("'{}@{}' is up to date ({})", language, version, previous),
+    }
+    Ok(())
+}
+
+fn sdk_update(target: Option<&str>) -> Result<()> {
+    let endpoint = registry_endpoint(&Config::load(&config_path()?)?);
+    let dir = sdk_dir()?;
+    let languages = match target.map(split_version).transpose()? {
+        Some((language, Some(requested))) => {
+            return update_version(language, &find_version(language, requested)?, &endpoint);
+        }
+        Some((language, None)) if !dir.join(language).is_dir() => {
+            return Err(anyhow!("SDK '{}' is not installed", language));
+        }
+        Some((language, None)) => vec![language.to_string()],
+        None => languages_in(&dir),
+    };
+    let mut total = 0;
+    let mut failed = 0;
+    for language in languages {
+        for version in layout::versions(&dir.join(&language)) {
+            if let Ok(None) = Origin::load(&dir.join(&language).join(&version)) {
+                println!("Skipping '{}@{}': installed manually, no origin record", language, version);
+                continue;
+            }
+            total += 1;
+            if let Err(e) = update_version(&language, &version, &endpoint) {
+                eprintln!("Failed to update '{}@{}': {:#}", language, version, e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(anyhow!("{} of {} SDKs failed to update", failed, total));
+    }
+    Ok(())
+}
+
+fn wasi_linker(engine: &Engine) -> Result<Linker<Host>> {
+    let mut linker = Linker::new(engine);
+    wasmtime_wasi::add_to_linker(&mut linker, |host: &mut Host| &mut host.wasi)?;
+    Ok(linker)
+}
+
+// Checks that an SDK's runtime is a WASI command this build can instantiate,
+// compiling it into the cache on the way.
+fn validate_runtime(language: &str, sdk_path: &Path) -> Result<()> {
+    let manifest = load_manifest(language, sdk_path)?;
+    let wasm_path = manifest.wasm_path(sdk_path);
+    let mut head = Vec::new();
+    File::open(&wasm_path)
+        .and_then(|file| file.take(512).read_to_end(&mut head))
+        .map_err(|e| anyhow!("Cannot read runtime '{}': {}", wasm_path.display(), e))?;
+    // Text-format modules are accepted too, as they are by `run`.
+    let text = String::from_utf8_lossy(&head);
+    let text = text.trim_start();
+    if !head.starts_with(b"\0asm") && !text.starts_with('(') && !text.starts_with(";;") {
+        return Err(anyhow!("Runtime is not a WebAssembly module"));
+    }
+    let engine = cache::Profile::default().engine()?;
+    let module = cache::load_module(&engine, &wasm_path, &modules_cache_dir()?, None)
+        .map_err(|e| anyhow!("Invalid WebAssembly module: {:#}", e))?;
+    match module.get_export(&manifest.entry) {
+        Some(ExternType::Func(ty)) if ty.params().len() == 0 && ty.results().len() == 0 => {}
+        _ => {
+            return Err(anyhow!(
+                "Runtime is not a WASI command: it does not export a '{}' function taking no arguments",
+                manifest.entry
+            ))
+        }
+    }
+    let linker = wasi_linker(&engine)?;
+    let mut store = Store::new(
+        &engine,
+        Host {
+            wasi: WasiCtxBuilder::new().build(),
+            limiter: MemoryLimiter {
+                max_memory: None,
+                exceeded: false,
+            },
+        },
+    );
+    let missing: Vec<String> = module
+        .imports()
+        .filter(|import| linker.get(&mut store, import.module(), import.name()).is_none())
+        .map(|import| format!("{}::{}", import.module(), import.name()))
+        .collect();
+    if !missing.is_empty() {
+        return Err(anyhow!(
+            "Runtime imports functions rchidrun does not provide: {}",
+            missing.join(", ")
+        ));
+    }
+    if !matches!(module.get_export("memory"), Some(ExternType::Memory(_))) {
+        eprintln!(
+            "Warning: runtime for '{}' does not export 'memory'; WASI calls that pass buffers will fail",
+            language
+        );
+    }
+    Ok(())
+}
+
+fn run_sdk(language: &str, sdk_path: &Path, script: &str, opts: &RunOptions) -> Result<i32> {
+    let manifest = load_manifest(language, sdk_path)?;
+    let wasm_path = manifest.wasm_path(sdk_path);
+    let script_path = fs::canonicalize(script).map_err(|e| anyhow!("Cannot open script '{}': {}", script, e))?;
+    let script_dir = script_path.parent().ok_or(anyhow!("Script has no parent directory"))?;
+    let script_name = script_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or(anyhow!("Invalid script file name"))?;
+    let guest_script = format!("{}/{}", SCRIPT_GUEST_DIR, script_name);
+    let argv = manifest.argv(&guest_script, &opts.args);
+    let mut vars = manifest.env.clone();
+    vars.extend(opts.env.iter().cloned());
+    let vars: Vec<(String, String)> = vars.into_iter().collect();
+
+    let profile = cache::Profile {
+        fuel: opts.limits.fuel.is_some(),
+        epoch: opts.limits.timeout.is_some(),
+    };
+    let engine = profile.engine()?;
+    let verified = opts.locked_runtime.as_ref().filter(|(path, _)| *path == wasm_path).map(|(_, d)| d.as_str());
+    let module = cache::load_module(&engine, &wasm_path, &modules_cache_dir()?, verified)?;
+    let wasi = WasiCtxBuilder::new()
+        .inherit_stdio()
+        .args(&argv)?
+        .envs(&vars)?
+        .build();
+    for mount in &manifest.mounts {
+        preopen(&wasi, &sdk_path.join(&mount.host), &mount.guest, !mount.writable)?;
+    }
+    preopen(&wasi, script_dir, SCRIPT_GUEST_DIR, false)?;
+    if opts.cwd {
+        preopen(&wasi, &env::current_dir()?, CWD_GUEST_DIR, false)?;
+    }
+    for mount in &opts.mounts {
+        preopen(&wasi, &mount.host, &mount.guest, mount.read_only)?;
+    }
+    let limiter = MemoryLimiter {
+        max_memory: opts.limits.max_memory,
+        exceeded: false,
+    };
+    let mut store = Store::new(&engine, Host { wasi, limiter });
+    store.limiter(|host| &mut host.limiter);
+    if let Some(fuel) = opts.limits.fuel {
+        store.add_fuel(fuel)?;
+    }
+    let linker = wasi_linker(&engine)?;
+    // Dropped when the run ends, which stops the watchdog.
+    let (_running, finished) = mpsc::channel::<()>();
+    if let Some(timeout) = opts.limits.timeout {
+        store.set_epoch_deadline(1);
+        let engine = engine.clone();
+        thread::spawn(move || {
+            if finished.recv_timeout(timeout) != Err(RecvTimeoutError::Timeout) {
+                return;
+            }
+            engine.increment_epoch();
+            // The epoch only interrupts running WebAssembly; a guest blocked in
+            // a host call (reading stdin, sleeping) never reaches a check.
+            if finished.recv_timeout(TIMEOUT_GRACE) != Err(RecvTimeoutError::Timeout) {
+                return;
+            }
+            eprintln!("Fatal error: {}: the script is blocked in a host call", Failure::Timeout);
+            let _ = io::stdout().flush();
+            process::exit(Failure::Timeout.exit_code());
+        });
+    }
+    let result = linker.instantiate(&mut store, &module).and_then(|instance| {
+        let start = instance
+            .get_func(&mut store, &manifest.entry)
+            .ok_or(anyhow!("{} function not found", manifest.entry))?;
+        start.call(&mut store, &[], &mut [])
+    });
+    match result {
+        Ok(()) => Ok(0),
+        Err(e) => {
+            if let Some(exit) = e.downcast_ref::<I32Exit>() {
+                return Ok(exit.0);
+            }
+            // Link errors and a missing entry point are not the guest's
+            // doing and keep the general exit code.
+            let failure = match e.downcast_ref::<Trap>() {
+                _ if store.data().limiter.exceeded => Failure::MemoryLimit,
+                Some(Trap::Interrupt) => Failure::Timeout,
+                Some(Trap::OutOfFuel) => Failure::FuelExhausted,
+                Some(_) => Failure::WasmTrap,
+                None => return Err(e),
+            };
+            Err(e.context(failure))
+        }
+    }
+}
+
+fn run_language(target: &str, script: &str, opts: &RunOptions, prompt: Prompt, pin: Option<&SdkPin>) -> Result<i32> {
+    let (language, requested) = split_version(target)?;
+    if let Some(sdk_path) = installed_sdk(language, requested)? {
+        if !runtime_path(language, &sdk_path)?.exists() && package::find_container(&sdk_path).is_some() {
+            // Unpacking writes into the installed SDK, so it must not race an
+            // update or removal of it; check again once the lock is held.
+            let _lock = lock_plugins()?;
+            if let (Some(container), false) =
+                (package::find_container(&sdk_path), runtime_path(language, &sdk_path)?.exists())
+            {
+                package::unpack(&container, &sdk_path, None).context(Failure::InstallFailed)?;
+            }
+        }
+        if runtime_path(language, &sdk_path)?.exists() {
+            return run_sdk(language, &sdk_path, script, opts);
+        }
+    }
+    let known = pin.is_some_and(|p| p.wasmer.is_some() || p.url.is_some() || p.file.is_some())
+        || is_supported_language(language);
+    if !known {
+        if let Some(suggestion) = suggest_language(language, &Config::load(&config_path()?)?) {
+            return Err(anyhow!(
+                "Unknown language '{}'. Did you mean '{}'? To add '{}' as a new language, \
+                 install it with 'rchidrun sdk install {} --url URL'",
+                language,
+                suggestion,
+                language,
+                language
+            )
+            .context(Failure::RuntimeMissing));
+        }
+    }
+    if prompt == Prompt::Never || (prompt == Prompt::Yes && !known) {
+        return Err(anyhow!(
+            "No runtime found for '{}'. Install it with 'rchidrun sdk install {}{}'",
+            target,
+            target,
+            if known { "" } else { " --url URL" }
+        )
+        .context(Failure::RuntimeMissing));
+    }
+    let installed = if known {
+        let source = pin.and_then(|p| p.url.clone().or(p.file.as_ref().map(|f| f.display().to_string())));
+        let question = match source {
+            Some(source) => format!("Install it from {}?", source),
+            None => "Install it via Wasmer?".to_string(),
+        };
+        if !confirm(prompt, &format!("No runtime found for '{}'.", target), &question)? {
+            return Err(anyhow!("Installation aborted").context(Failure::RuntimeMissing));
+        }
+        let _lock = lock_plugins()?;
+        match installed_sdk(language, requested)? {
+            Some(sdk_path) => Ok(sdk_path),
+            None => install_pinned(language, requested, pin),
+        }
+    } else {
+        println!("No runtime found for '{}'.", target);
+        print!("Language not predefined. Provide a URL to the WASM runtime: ");
+        io::stdout().flush()?;
+        let url = read_line()?;
+        let _lock = lock_plugins()?;
+        install_via_url(language, requested.unwrap_or(layout::UNVERSIONED), &url, &Verify::default())
+    };
+    let sdk_path = installed.context(Failure::InstallFailed)?;
+    run_sdk(language, &sdk_path, script, opts)
+}
+
+fn confirm(prompt: Prompt, notice: &str, question: &str) -> Result<bool> {
+    match prompt {
+        Prompt::Yes => Ok(true),
+        Prompt::Never => Ok(false),
+        Prompt::Ask => {
+            println!("{}", notice);
+            print!("{} (y/n): ", question);
+            io::stdout().flush()?;
+            Ok(read_line()?.to_lowercase() == "y")
+        }
+    }
+}
+
+fn install_pinned(language: &str, requested: Option<&str>, pin: Option<&SdkPin>) -> Result<PathBuf> {
+    let version = requested.unwrap_or(layout::UNVERSIONED);
+    let verify = Verify {
+        sha256: pin.and_then(|p| p.sha256.clone()),
+        signature: pin.and_then(|p| p.signature.clone()),
+    };
+    if let Some(url) = pin.and_then(|p| p.url.as_deref()) {
+        return install_via_url(language, version, url, &verify);
+    }
+    if let Some(file) = pin.and_then(|p| p.file.as_deref()) {
+        return install_from_file(language, version, file, &verify);
+    }
+    let entry = catalog().get(language);
+    if let Some(package) = pin.and_then(|p| p.wasmer.as_deref()).or(entry.and_then(|e| e.wasmer.as_deref())) {
+        return install_via_wasmer(language, &wasmer_spec(package, requested), true, &verify);
+    }
+    match entry.and_then(|e| e.url.as_deref().map(|url| (url, e))) {
+        Some((url, entry)) => {
+            let verify = Verify {
+                sha256: verify.sha256.clone().or(entry.sha256.clone()),
+                ..verify
+            };
+            install_via_url(language, version, url, &verify)
+        }
+        None => Err(anyhow!("No package known for '{}'; pin a wasmer, url or file source", language)),
+    }
+}
+
+// Returns the installed SDK matching a lockfile entry, and the digest of its
+// runtime as just re-hashed (None while a package is still to be unpacked).
+fn verify_locked(locked: &LockedSdk) -> Result<Option<(PathBuf, Option<String>)>> {
+    let roots = plugin_roots()?;
+    let found = roots.iter().map(|root| root.join(&locked.language).join(&locked.version)).find(|p| p.is_dir());
+    let Some(sdk_path) = found else { return Ok(None) };
+    let origin = match Origin::load(&sdk_path)? {
+        Some(origin) if origin.sha256 == locked.sha256 => origin,
+        other => {
+            return Err(anyhow!(
+                "SDK '{}@{}' does not match {} (expected sha256 {}, found {}). \
+                 Reinstall it with 'rchidrun sdk install {} --locked --force'",
+                locked.language,
+                locked.version,
+                LOCK_FILE,
+                locked.sha256,
+                other.as_ref().map_or("no origin record", |o| o.sha256.as_str()),
+                locked.language
+            ))
+        }
+    };
+    if let Some(file) = modified_file(&locked.language, &sdk_path, &origin)? {
+        return Err(anyhow!(
+            "SDK '{}@{}' has been modified since it was installed: '{}' was changed, added or removed. \
+             Reinstall it with 'rchidrun sdk install {} --locked --force'",
+            locked.language,
+            locked.version,
+            file,
+            locked.language
+        ));
+    }
+    // Every file was just compared with the recorded digests, so the
+    // runtime's record is its current digest.
+    let runtime = runtime_path(&locked.language, &sdk_path)?;
+    let name = runtime.strip_prefix(&sdk_path).unwrap_or(&runtime).to_string_lossy().into_owned();
+    let digest = match origin.files.get(&name) {
+        Some(digest) => Some(digest.clone()),
+        None if runtime.is_file() => Some(download::sha256_file(&runtime)?),
+        None => None,
+    };
+    Ok(Some((sdk_path, digest)))
+}
+
+// Re-hashes an installed SDK against the digests recorded when it was
+// installed. Older installs have none; their stored runtime or package, which
+// is the artifact the origin hash covers, is checked instead.
+fn modified_file(language: &str, sdk_path: &Path, origin: &Origin) -> Result<Option<String>> {
+    if origin.files.is_empty() {
+        let artifact = match package::find_container(sdk_path) {
+            Some(container) => container,
+            None => runtime_path(language, sdk_path)?,
+        };
+        let digest = download::sha256_file(&artifact).ok();
+        let name = artifact.strip_prefix(sdk_path).unwrap_or(&artifact).display().to_string();
+        return Ok((digest.as_deref() != Some(origin.sha256.as_str())).then_some(name));
+    }
+    let current = layout::digests(sdk_path)?;
+    let changed = origin
+        .files
+        .iter()
+        .find(|(name, digest)| current.get(*name) != Some(*digest))
+        .map(|(name, _)| name)
+        .or_else(|| current.keys().find(|name| !origin.files.contains_key(*name)));
+    Ok(changed.cloned())
+}
+
+// `root` is the project directory, which relative `file` sources are
+// resolved against.
+fn install_locked(locked: &LockedSdk, root: &Path, force: bool) -> Result<PathBuf> {
+    let language = &locked.language;
+    let verify = Verify {
+        sha256: Some(locked.sha256.clone()),
+        ..Verify::default()
+    };
+    prepare_install(language, &locked.version, force)?;
+    match &locked.source {
+        Source::Wasmer {
+            package,
+            constraint,
+            version,
+        } => {
+            let endpoint = registry_endpoint(&Config::load(&config_path()?)?);
+            let release = registry::resolve(&endpoint, &format!("{}@={}", package, version), &downloader()?)?;
+            let sdk_path = install_release(language, &release, constraint.clone(), &verify)?;
+            println!("Installed '{}@{}' via Wasmer ({})", language, release.version, release.name);
+            Ok(sdk_path)
+        }
+        Source::Url { url, .. } => install_via_url(language, &locked.version, url, &verify),
+        Source::File { path } => install_from_file(language, &locked.version, &root.join(path), &verify),
+    }
+}
+
+fn discover_project() -> Result<Project> {
+    let cwd = env::current_dir()?;
+    let mut project = Project::discover(&cwd)?.ok_or(anyhow!(
+        "No {} found in '{}' or its parents",
+        PROJECT_FILE,
+        cwd.display()
+    ))?;
+    let config = Config::load(&config_path()?)?;
+    project.canonicalize(|language| canonical_language(language, &config));
+    Ok(project)
+}
+
+fn sdk_install_locked(language: Option<&str>, force: bool) -> Result<()> {
+    let project = discover_project()?;
+    let lock_path = project.path(Path::new(LOCK_FILE));
+    let lock = Lock::load(&project.root)?.ok_or(anyhow!(
+        "No {} next to '{}'; create it with 'rchidrun lock'",
+        LOCK_FILE,
+        project.path(Path::new(PROJECT_FILE)).display()
+    ))?;
+    let entries: Vec<&LockedSdk> = match language {
+        Some(language) => vec![lock
+            .get(language)
+            .ok_or(anyhow!("'{}' is not locked in '{}'", language, lock_path.display()))?],
+        None => lock.sdks.iter().collect(),
+    };
+    for locked in entries {
+        if !force && verify_locked(locked)?.is_some() {
+            println!("'{}@{}' is up to date", locked.language, locked.version);
+            continue;
+        }
+        install_locked(locked, &project.root, force)?;
+    }
+    Ok(())
+}
+
+fn lock_project() -> Result<()> {
+    let project = discover_project()?;
+    let mut lock = Lock::default();
+    for language in project.languages() {
+        let pin = project.pin(&language);
+        let requested = pin.and_then(|p| p.version.as_deref());
+        let sdk_path = match installed_sdk(&language, requested)? {
+            Some(sdk_path) => sdk_path,
+            None => install_pinned(&language, requested, pin).context(Failure::InstallFailed)?,
+        };
+        let version = sdk_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or(anyhow!("Invalid SDK directory '{}'", sdk_path.display()))?
+            .to_string();
+        let origin = Origin::load(&sdk_path)?.ok_or(anyhow!(
+            "SDK '{}@{}' has no origin record and cannot be locked; reinstall it with 'rchidrun sdk install {}@{} --force'",
+            language,
+            version,
+            language,
+            version
+        ))?;
+        println!("Locked '{}@{}' ({})", language, version, origin.source);
+        // Files inside the project are locked by their path relative to it,
+        // so the lockfile works in any checkout.
+        let source = match origin.source {
+            Source::File { path } => Source::File {
+                path: project_relative(&project.root, &path),
+            },
+            source => source,
+        };
+        lock.sdks.push(LockedSdk {
+            language,
+            version,
+            sha256: origin.sha256,
+            source,
+        });
+    }
+    lock.save(&project.root)?;
+    println!("Wrote {}", project.path(Path::new(LOCK_FILE)).display());
+    Ok(())
+}
+
+fn project_relative(root: &Path, path: &str) -> String {
+    let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    match Path::new(path).strip_prefix(&root) {
+        Ok(relative) => relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        Err(_) => path.to_string(),
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+fn format_age(installed_at: u64) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let age = now.saturating_sub(installed_at);
+    match age {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{} minutes ago", age / 60),
+        3600..=86399 => format!("{} hours ago", age / 3600),
+        _ => format!("{} days ago", age / 86400),
+    }
+}
+
+fn format_memory(ty: &MemoryType) -> String {
+    const PAGE: u64 = 64 * 1024;
+    let minimum = format!("{} pages ({})", ty.minimum(), format_size(ty.minimum() * PAGE));
+    match ty.maximum() {
+        Some(maximum) => format!("{} minimum, {} pages ({}) maximum", minimum, maximum, format_size(maximum * PAGE)),
+        None => format!("{} minimum, no maximum", minimum),
+    }
+}
+
+fn extern_kind(ty: &ExternType) -> &'static str {
+    if ty.func().is_some() {
+        "function"
+    } else if ty.memory().is_some() {
+        "memory"
+    } else if ty.table().is_some() {
+        "table"
+    } else {
+        "global"
+    }
+}
+
+fn sdk_info(target: &str) -> Result<()> {
+    let (language, requested) = split_version(target)?;
+    let sdk_path = match (installed_sdk(language, requested)?, requested) {
+        (Some(sdk_path), _) => sdk_path,
+        (None, Some(requested)) => sdk_dir()?.join(language).join(find_version(language, requested)?),
+        (None, None) => return Err(anyhow!("SDK '{}' is not installed", language)),
+    };
+    let lang_dir = sdk_path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let version = sdk_path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+    let is_default = layout::default_version(&lang_dir).as_deref() == Some(version.as_str());
+    println!("{} {}{}", language, version, if is_default { " (default)" } else { "" });
+    println!("Directory: {}", sdk_path.display());
+
+    let custom = Manifest::load(&sdk_path)?.is_some();
+    let manifest = load_manifest(language, &sdk_path)?;
+    println!("Manifest: {}", if custom { manifest::MANIFEST_FILE } else { "built-in" });
+    println!("  Entry: {}", manifest.entry);
+    if !manifest.args.is_empty() {
+        println!("  Arguments: {}", manifest.args.join(" "));
+    }
+    for mount in &manifest.mounts {
+        let access = if mount.writable { "read-write" } else { "read-only" };
+        println!("  Mount: {} -> {} ({})", mount.host, mount.guest, access);
+    }
+    for (name, value) in &manifest.env {
+        println!("  Env: {}={}", name, value);
+    }
+
+    match Origin::load(&sdk_path)? {
+        Some(origin) => {
+            println!("Origin: {}", origin.source);
+            println!("  Installed: {}", format_age(origin.installed_at));
+            println!("  SHA-256: {}", origin.sha256);
+            if let Some(signature) = &origin.signature {
+                println!("  Signature: {}", signature);
+            }
+        }
+        None => println!("Origin: unknown (installed manually)"),
+    }
+
+    let wasm_path = manifest.wasm_path(&sdk_path);
+    let size = fs::metadata(&wasm_path)
+        .map_err(|e| anyhow!("Cannot read runtime '{}': {}", wasm_path.display(), e))?
+        .len();
+    let digest = download::sha256_file(&wasm_path)?;
+    let cached = cache::cached_profiles(&wasm_path, &digest, &modules_cache_dir()?);
+    println!("Runtime: {}", wasm_path.display());
+    println!("  Size: {}", format_size(size));
+    println!("  SHA-256: {}", digest);
+    if cached.is_empty() {
+        println!("  Compiled cache: none");
+    } else {
+        println!("  Compiled cache: {}", cached.join(", "));
+    }
+
+    let engine = cache::Profile::default().engine()?;
+    let module = cache::load_module(&engine, &wasm_path, &modules_cache_dir()?, None)?;
+    let mut imports: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for import in module.imports() {
+        if let Some(memory) = import.ty().memory() {
+            println!("  Memory (imported {}::{}): {}", import.module(), import.name(), format_memory(memory));
+        }
+        imports.entry(import.module().to_string()).or_default().push(import.name().to_string());
+    }
+    for export in module.exports() {
+        if let Some(memory) = export.ty().memory() {
+            println!("  Memory ({}): {}", export.name(), format_memory(memory));
+        }
+    }
+    let wasi = match (imports.contains_key("wasi_snapshot_preview1"), imports.contains_key("wasi_unstable")) {
+        (true, _) => "preview1 (wasi_snapshot_preview1)",
+        (false, true) => "preview0 (wasi_unstable)",
+        (false, false) => "none",
+    };
+    println!("  WASI: {}", wasi);
+
+    println!("Imports:");
+    for (module_name, names) in &imports {
+        println!("  {} ({}): {}", module_name, names.len(), names.join(", "));
+    }
+    println!("Exports:");
+    for export in module.exports() {
+        println!("  {} ({})", export.name(), extern_kind(&export.ty()));
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum SdkStatus {
+    Installed,
+    Broken,
+    Available,
+}
+
+// One row of `sdk list`: an installed version, a language directory or
+// version that cannot run, or a supported language that is not installed.
+#[derive(Serialize)]
+struct SdkEntry {
+    language: String,
+    status: SdkStatus,
+    version: Option<String>,
+    default: bool,
+    source: Option<String>,
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    problem: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    aliases: Vec<String>,
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else { return 0 };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&entry.path()),
+            Ok(_) => entry.metadata().map_or(0, |m| m.len()),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+fn version_entry(language: &str, lang_dir: &Path, version: &str, default: bool) -> SdkEntry {
+    let sdk_path = lang_dir.join(version);
+    let origin = Origin::load(&sdk_path).ok().flatten();
+    let problem = match load_manifest(language, &sdk_path) {
+        Err(e) => Some(e.to_string()),
+        Ok(manifest) if !manifest.wasm_path(&sdk_path).is_file() => Some(format!("missing {}", manifest.wasm)),
+        Ok(_) => None,
+    };
+    SdkEntry {
+        language: language.to_string(),
+        status: if problem.is_some() { SdkStatus::Broken } else { SdkStatus::Installed },
+        version: Some(version.to_string()),
+        default,
+        source: Some(origin.map_or("manual".to_string(), |o| o.source.to_string())),
+        size: Some(dir_size(&sdk_path)),
+        path: Some(sdk_path),
+        problem,
+        aliases: Vec::new(),
+    }
+}
+
+fn sdk_entries(config: &Config) -> Result<Vec<SdkEntry>> {
+    let mut entries = Vec::new();
+    for root in plugin_roots()? {
+        for language in languages_in(&root) {
+            let lang_dir = root.join(&language);
+            // Only the version `run` would pick is the default, even when
+            // several roots have one.
+            let default = installed_sdk(&language, None)?;
+            let versions = layout::versions(&lang_dir);
+            if versions.is_empty() {
+                entries.push(SdkEntry {
+                    language: language.clone(),
+                    status: SdkStatus::Broken,
+                    version: None,
+                    default: false,
+                    source: catalog().get(&language).and_then(|e| e.source()).map(String::from),
+                    size: Some(dir_size(&lang_dir)),
+                    path: Some(lang_dir.clone()),
+                    problem: Some("no runtime installed".to_string()),
+                    aliases: Vec::new(),
+                });
+            }
+            for version in versions {
+                let is_default = default.as_deref() == Some(lang_dir.join(&version).as_path());
+                entries.push(version_entry(&language, &lang_dir, &version, is_default));
+            }
+        }
+    }
+    for (language, entry) in &catalog().languages {
+        let Some(source) = entry.source() else { continue };
+        if !entries.iter().any(|e| &e.language == language) {
+            entries.push(SdkEntry {
+                language: language.clone(),
+                status: SdkStatus::Available,
+                version: None,
+                default: false,
+                source: Some(source.to_string()),
+                size: None,
+                path: None,
+                problem: None,
+                aliases: Vec::new(),
+            });
+        }
+    }
+    // Installed languages come out of `installed_languages` sorted and their
+    // versions in version order; keep that and only order languages by name.
+    entries.sort_by(|a, b| a.language.cmp(&b.language));
+    for entry in &mut entries {
+        entry.aliases = aliases_of(&entry.language, config);
+    }
+    Ok(entries)
+}
+
+fn sdk_list(json: bool, config: &Config) -> Result<()> {
+    let entries = sdk_entries(config)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+    let rows: Vec<[String; 5]> = entries
+        .iter()
+        .map(|e| {
+            let status = match e.status {
+                SdkStatus::Installed => "installed",
+                SdkStatus::Broken => "broken",
+                SdkStatus::Available => "available",
+            };
+            let version = match &e.version {
+                Some(version) if e.default => format!("{} (default)", version),
+                Some(version) => version.clone(),
+                None => "-".to_string(),
+            };
+            let detail = match (&e.problem, &e.source) {
+                (Some(problem), _) => problem.clone(),
+                (None, Some(source)) => source.clone(),
+                (None, None) => String::new(),
+            };
+            let size = e.size.filter(|_| e.version.is_some()).map_or("-".to_string(), format_size);
+            [e.language.clone(), version, status.to_string(), size, detail]
+        })
+        .collect();
+    let header = ["LANGUAGE", "VERSION", "STATUS", "SIZE", "SOURCE"].map(String::from);
+    let mut widths = [0; 4];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    for row in std::iter::once(&header).chain(&rows) {
+        println!(
+            "{:w0$}  {:w1$}  {:w2$}  {:w3$}  {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            row[4],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3]
+        );
+    }
+    Ok(())
+}
+
+fn sdk_search(term: &str) -> Result<()> {
+    let matches = catalog().search(term);
+    if matches.is_empty() {
+        println!("No languages in the catalog match '{}'", term);
+        return Ok(());
+    }
+    for (language, entry) in matches {
+        let installed = if is_installed_language(language) { " (installed)" } else { "" };
+        println!("{}{} - {}", language, installed, entry.source().unwrap_or("no install source"));
+        if let Some(description) = &entry.description {
+            println!("  {}", description);
+        }
+        if !entry.aliases.is_empty() {
+            println!("  Aliases: {}", entry.aliases.join(", "));
+        }
+        if !entry.extensions.is_empty() {
+            let extensions: Vec<String> = entry.extensions.iter().map(|e| format!(".{}", e.trim_start_matches('.'))).collect();
+            println!("  Extensions: {}", extensions.join(", "));
+        }
+    }
+    Ok(())
+}
+
+fn migrate_installs() {
+    let Ok(dir) = sdk_dir() else { return };
+    let flat: Vec<String> = languages_in(&dir)
+        .into_iter()
+        .filter(|language| layout::is_flat(&dir.join(language)))
+        .collect();
+    if flat.is_empty() {
+        return;
+    }
+    let Ok(_lock) = lock_plugins() else { return };
+    for language in flat {
+        let lang_dir = dir.join(&language);
+        if layout::is_flat(&lang_dir) {
+            match layout::migrate(&lang_dir) {
+                Ok(path) => eprintln!("Moved '{}' to the versioned layout at {}", language, path.display()),
+                Err(e) => eprintln!("Warning: could not migrate '{}' to the versioned layout: {}", language, e),
+            }
+        }
+    }
+}
+
+impl RunArgs {
+    fn split(&self, config: &Config) -> (Option<String>, String, Vec<String>) {
+        match self.args.split_first() {
+            Some((script, rest)) if looks_like_language(&self.target, config) => {
+                (Some(self.target.clone()), script.clone(), strip_separator(rest))
+            }
+            _ => (None, self.target.clone(), strip_separator(&self.args)),
+        }
+    }
+
+    fn options(&self, args: Vec<String>, project: Option<&Project>) -> Result<RunOptions> {
+        let mut mounts = Vec::new();
+        let mut env = BTreeMap::new();
+        let mut limits = Limits {
+            timeout: self.timeout,
+            max_memory: self.max_memory,
+            fuel: self.fuel,
+        };
+        if let Some(project) = project {
+            let defaults = &project.run;
+            let specs = defaults.dir.iter().map(|s| (s, false));
+            for (spec, read_only) in specs.chain(defaults.dir_ro.iter().map(|s| (s, true))) {
+                let mut mount = parse_mount(spec, read_only)?;
+                mount.host = project.path(&mount.host);
+                mounts.push(mount);
+            }
+            let env_files: Vec<PathBuf> = defaults.env_file.iter().map(|f| project.path(f)).collect();
+            env.extend(build_env(&[], &env_files, &defaults.inherit_env)?);
+            env.extend(defaults.env.clone());
+            let file = project.path(Path::new(project::PROJECT_FILE));
+            if limits.timeout.is_none() {
+                limits.timeout = defaults
+                    .timeout
+                    .as_deref()
+                    .map(parse_duration)
+                    .transpose()
+                    .map_err(|e| anyhow!("Invalid timeout in '{}': {}", file.display(), e))?;
+            }
+            if limits.max_memory.is_none() {
+                limits.max_memory = defaults
+                    .max_memory
+                    .as_deref()
+                    .map(parse_size)
+                    .transpose()
+                    .map_err(|e| anyhow!("Invalid max-memory in '{}': {}", file.display(), e))?;
+            }
+            limits.fuel = limits.fuel.or(defaults.fuel);
+        }
+        for spec in &self.dirs {
+            mounts.push(parse_mount(spec, false)?);
+        }
+        for spec in &self.dirs_ro {
+            mounts.push(parse_mount(spec, true)?);
+        }
+        env.extend(build_env(&self.envs, &self.env_files, &self.inherit_env)?);
+        Ok(RunOptions {
+            mounts,
+            cwd: self.cwd || project.is_some_and(|p| p.run.cwd),
+            args,
+            env: env.into_iter().collect(),
+            limits,
+            locked_runtime: None,
+        })
+    }
+}
+
+fn strip_separator(args: &[String]) -> Vec<String> {
+    match args.split_first() {
+        Some((first, rest)) if first == "--" => rest.to_vec(),
+        _ => args.to_vec(),
+    }
+}
+
+fn run_script(args: &RunArgs, prompt: Prompt) -> Result<i32> {
+    let mut config = Config::load(&config_path()?)?;
+    let (language, script, script_args) = args.split(&config);
+    let script_dir = match fs::canonicalize(&script) {
+        Ok(path) => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+        Err(_) => env::current_dir()?,
+    };
+    let mut project = Project::discover(&script_dir)?;
+    if let Some(project) = &mut project {
+        project.canonicalize(|language| canonical_language(language, &config));
+        config.extensions.extend(project.extensions.clone());
+    }
+    let language = match language {
+        Some(language) => canonical_target(&language, &config)?,
+        None => canonical_target(&detect_language(&script, &config)?, &config)?,
+    };
+    let (name, requested) = split_version(&language)?;
+    let pin = project.as_ref().and_then(|p| p.pin(name));
+    let lock = match &project {
+        Some(project) => Lock::load(&project.root)?.map(|lock| (lock, project.root.as_path())),
+        None => None,
+    };
+    let mut locked_runtime = None;
+    let target = if let Some((locked, root)) = lock.as_ref().and_then(|(lock, root)| Some((lock.get(name)?, *root))) {
+        if requested.is_some_and(|r| !layout::matches(&locked.version, r)) {
+            return Err(anyhow!(
+                "'{}' conflicts with {}, which locks '{}' to {}",
+                language,
+                LOCK_FILE,
+                name,
+                locked.version
+            ));
+        }
+        let mut verified = verify_locked(locked)?;
+        if verified.is_none() {
+            let notice = format!("SDK '{}@{}' from {} is not installed.", name, locked.version, LOCK_FILE);
+            if !confirm(prompt, &notice, "Install it?")? {
+                return Err(anyhow!("{} Install it with 'rchidrun sdk install --locked'", notice)
+                    .context(Failure::RuntimeMissing));
+            }
+            let _lock = lock_plugins()?;
+            if verify_locked(locked)?.is_none() {
+                install_locked(locked, root, false).context(Failure::InstallFailed)?;
+            }
+            verified = verify_locked(locked)?;
+        }
+        if let Some((sdk_path, Some(digest))) = verified {
+            locked_runtime = Some((runtime_path(name, &sdk_path)?, digest));
+        }
+        format!("{}@{}", name, locked.version)
+    } else {
+        match pin.and_then(|p| p.version.as_ref()) {
+            Some(version) if requested.is_none() => format!("{}@{}", name, version),
+            _ => language.clone(),
+        }
+    };
+    let mut opts = args.options(script_args, project.as_ref())?;
+    opts.locked_runtime = locked_runtime;
+    run_language(&target, &script, &opts, prompt, pin)
+}
+
+fn run(cli: Cli) -> Result<i32> {
+    let prompt = if cli.yes {
+        Prompt::Yes
+    } else if cli.no_input || !io::stdin().is_terminal() {
+        Prompt::Never
+    } else {
+        Prompt::Ask
+    };
+    migrate_installs();
+    // Commands that change installed SDKs hold the plugin lock throughout;
+    // `run` only takes it when it has to install something.
+    let _lock = match &cli.command {
+        Commands::Lock => Some(lock_plugins()?),
+        Commands::Sdk { command }
+            if !matches!(
+                command,
+                SdkCommands::List { .. }
+                    | SdkCommands::Info { .. }
+                    | SdkCommands::Search { .. }
+                    | SdkCommands::Export { .. }
+            ) =>
+        {
+            Some(lock_plugins()?)
+        }
+        _ => None,
+    };
+    let config = Config::load(&config_path()?)?;
+    let _ = CATALOG.set(load_catalog(&config)?);
+    match cli.command {
+        Commands::Run(args) => run_script(&args, prompt),
+        Commands::Lock => lock_project().map(|()| 0),
+        Commands::SdkList => sdk_list(false, &config).map(|()| 0),
+        Commands::Sdk { command } => match command {
+            SdkCommands::List { json } => sdk_list(json, &config).map(|()| 0),
+            SdkCommands::Search { term } => sdk_search(term.as_deref().unwrap_or("")).map(|()| 0),
+            SdkCommands::Info { language } => sdk_info(&canonical_target(&language, &config)?).map(|()| 0),
+            SdkCommands::Install(mut args) => {
+                args.language = args.language.map(|target| canonical_target(&target, &config)).transpose()?;
+                sdk_install(&args).context(Failure::InstallFailed).map(|()| 0)
+            }
+            SdkCommands::Export { language, output } => {
+                sdk_export(&canonical_target(&language, &config)?, output.as_deref()).map(|()| 0)
+            }
+            SdkCommands::Import { bundle, force } => {
+                sdk_import(&bundle, force).context(Failure::InstallFailed).map(|()| 0)
+            }
+            SdkCommands::Remove { language } => sdk_remove(&canonical_target(&language, &config)?).map(|()| 0),
+            SdkCommands::Default { language, version } => {
+                sdk_default(&canonical_language(&language, &config), &version).map(|()| 0)
+            }
+            SdkCommands::Update { language, .. } => {
+                let target = language.map(|target| canonical_target(&target, &config)).transpose()?;
+                sdk_update(target.as_deref()).context(Failure::InstallFailed).map(|()| 0)
+            }
+        },
+        Commands::Script(argv) => {
+            let (target, args) = argv.split_first().ok_or(anyhow!("No script given"))?;
+            if !Path::new(target).is_file() {
+                return Err(anyhow!("Unknown command or script '{}'. Run 'rchidrun --help' for usage.", target));
+            }
+            run_script(&RunArgs {
+                target: target.clone(),
+                args: args.to_vec(),
+                ..Default::default()
+            }, prompt)
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let code = match run(cli) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Fatal error: {:#}", e);
+            e.downcast_ref::<Failure>().map_or(EXIT_FAILURE, |f| f.exit_code())
+        }
+    };
+    let _ = io::stdout().flush();
+    process::exit(code);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_mount_maps_host_to_guest() {
+        let mount = parse_mount("data:/data", true).unwrap();
+        assert_eq!(mount.host, PathBuf::from("data"));
+        assert_eq!(mount.guest, "/data");
+        assert!(mount.read_only);
+    }
+
+    #[test]
+    fn parse_mount_without_guest_uses_host_path() {
+        let mount = parse_mount("/srv/data", false).unwrap();
+        assert_eq!(mount.host, PathBuf::from("/srv/data"));
+        assert_eq!(mount.guest, "/srv/data");
+        assert!(!mount.read_only);
+    }
+
+    #[test]
+    fn parse_mount_splits_at_first_colon() {
+        let mount = parse_mount("data:/a:b", false).unwrap();
+        assert_eq!(mount.host, PathBuf::from("data"));
+        assert_eq!(mount.guest, "/a:b");
+    }
+
+    #[test]
+    fn parse_mount_rejects_empty_parts() {
+        for spec in ["", ":", ":/data", "data:"] {
+            assert!(parse_mount(spec, false).is_err(), "{:?}", spec);
+        }
+    }
+
+    #[test]
+    fn matches_pattern_globs() {
+        assert!(matches_pattern("HOME", "HOME"));
+        assert!(!matches_pattern("HOME", "HOMES"));
+        assert!(matches_pattern("AWS_*", "AWS_REGION"));
+        assert!(matches_pattern("AWS_*", "AWS_"));
+        assert!(matches_pattern("*", ""));
+        assert!(matches_pattern("A*B*C", "AxxBxC"));
+        assert!(!matches_pattern("A*C", "AB"));
+        assert!(matches_pattern("É*É", "ÉtÉ"));
+    }
+
+    #[test]
+    fn unquote_handles_quoting() {
+        assert_eq!(unquote(""), "");
+        assert_eq!(unquote("  plain  "), "plain");
+        assert_eq!(unquote("value # comment"), "value");
+        assert_eq!(unquote("a#b"), "a#b");
+        assert_eq!(unquote("'a # b'"), "a # b");
+        assert_eq!(unquote(r"'a\nb'"), r"a\nb");
+        assert_eq!(unquote(r#""a\nb\t\"c\"""#), "a\nb\t\"c\"");
+        assert_eq!(unquote(r#""a" # note"#), "a");
+        assert_eq!(unquote("'a'#note"), "a");
+        assert_eq!(unquote(r#""trailing\""#), r#""trailing\""#);
+        assert_eq!(unquote(r#""a"b"#), r#""a"b"#);
+        assert_eq!(unquote("''"), "");
+        assert_eq!(unquote("\""), "\"");
+        assert_eq!(unquote("'open"), "'open");
+    }
+
+    #[test]
+    fn parse_env_file_reads_assignments() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "# comment\n\nA=1\nexport B = 'two words'\n  C=\"x=y\" # note\nD=").unwrap();
+        let vars = parse_env_file(file.path()).unwrap();
+        let expected = [("A", "1"), ("B", "two words"), ("C", "x=y"), ("D", "")];
+        assert_eq!(vars, expected.map(|(k, v)| (k.to_string(), v.to_string())));
+    }
+
+    #[test]
+    fn parse_env_file_rejects_bad_lines() {
+        for content in ["A=1\nnot an assignment\n", "A=1\n=value\n"] {
+            let mut file = tempfile::NamedTempFile::new().unwrap();
+            file.write_all(content.as_bytes()).unwrap();
+            let err = parse_env_file(file.path()).unwrap_err().to_string();
+            assert!(err.ends_with(":2: expected KEY=VALUE"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn parse_env_file_accepts_empty_file() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        assert!(parse_env_file(file.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration(" 1.5s "), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        assert_eq!(parse_duration("0"), Ok(Duration::ZERO));
+    }
+
+    #[test]
+    fn parse_duration_rejects_bad_input() {
+        for value in ["", "s", ".", "1.2.3s", "-1s", "5d", "1e3s", "5 s"] {
+            assert!(parse_duration(value).is_err(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn parse_duration_overflow() {
+        let huge = format!("{}h", "9".repeat(30));
+        assert_eq!(parse_duration(&huge), Err(format!("duration '{}' is too long", huge)));
+        let infinite = format!("{}s", "9".repeat(400));
+        assert!(parse_duration(&infinite).is_err());
+    }
+
+    #[test]
+    fn parse_size_units() {
+        assert_eq!(parse_size("100"), Ok(100));
+        assert_eq!(parse_size("100B"), Ok(100));
+        assert_eq!(parse_size("512k"), Ok(512 << 10));
+        assert_eq!(parse_size("64MB"), Ok(64 << 20));
+        assert_eq!(parse_size("1GiB"), Ok(1 << 30));
+        assert_eq!(parse_size(" 2g "), Ok(2 << 30));
+    }
+
+    #[test]
+    fn parse_size_rejects_bad_input() {
+        for value in ["", "M", "1.5M", "-1", "10T", "5KBB", "5iB", "5 M"] {
+            assert!(parse_size(value).is_err(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn parse_size_overflow() {
+        let value = format!("{}G", usize::MAX);
+        assert_eq!(parse_size(&value), Err(format!("size '{}' is too large", value)));
+        let value = format!("{}0", usize::MAX);
+        assert_eq!(parse_size(&value), Err(format!("size '{}' is too large", value)));
+    }
+
+    #[test]
+    fn parse_sha256_normalizes_case() {
+        let digest = "AB".repeat(32);
+        assert_eq!(parse_sha256(&digest), Ok("ab".repeat(32)));
+        assert!(parse_sha256("").is_err());
+        assert!(parse_sha256(&"g".repeat(64)).is_err());
+        assert!(parse_sha256(&"a".repeat(63)).is_err());
+    }
+
+    fn config_with_aliases(aliases: &[(&str, &str)]) -> Config {
+        Config {
+            aliases: aliases.iter().map(|(a, l)| (a.to_string(), l.to_string())).collect(),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn edit_distance_counts_edits() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("", "ruby"), 4);
+        assert_eq!(edit_distance("ruby", ""), 4);
+        assert_eq!(edit_distance("python", "python"), 0);
+        assert_eq!(edit_distance("pyhton", "python"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("héllo", "hello"), 1);
+    }
+
+    #[test]
+    fn canonical_language_resolves_aliases() {
+        let config = config_with_aliases(&[("Py2", "Python"), ("j", "NODE"), ("l", "Lua")]);
+        let installed = ["Zig".to_string()];
+        let canonical = |name| canonical_language_among(name, &config, &installed);
+        assert_eq!(canonical("PY"), "python");
+        assert_eq!(canonical("Python"), "python");
+        assert_eq!(canonical("py2"), "python");
+        assert_eq!(canonical("J"), "javascript");
+        assert_eq!(canonical("l"), "lua");
+        assert_eq!(canonical("zig"), "Zig");
+        assert_eq!(canonical("NoSuchLanguage"), "NoSuchLanguage");
+    }
+
+    #[test]
+    fn aliases_of_resolves_configured_targets() {
+        let config = config_with_aliases(&[("Py2", "Python"), ("j", "NODE")]);
+        assert!(aliases_of("python", &config).contains(&"py2".to_string()));
+        assert!(aliases_of("javascript", &config).contains(&"j".to_string()));
+        assert!(!aliases_of("python", &config).contains(&"j".to_string()));
+    }
+
+    #[test]
+    fn canonical_target_keeps_the_version() {
+        let config = Config::default();
+        assert_eq!(canonical_target("node@20.1", &config).unwrap(), "javascript@20.1");
+        assert!(canonical_target("py@../x", &config).is_err());
+    }
+
+    #[test]
+    fn suggest_language_finds_close_names() {
+        let config = config_with_aliases(&[("golang", "go")]);
+        let installed = ["Zig".to_string()];
+        let suggest = |name| suggest_language_among(name, &config, &installed);
+        assert_eq!(suggest("pyhton").as_deref(), Some("python"));
+        assert_eq!(suggest("Rubyy").as_deref(), Some("ruby"));
+        assert_eq!(suggest("golag").as_deref(), Some("go"));
+        assert_eq!(suggest("zigg").as_deref(), Some("Zig"));
+        assert_eq!(suggest(""), None);
+        assert_eq!(suggest("qqqqqqqqqq"), None);
+    }
+
+    #[test]
+    fn project_relative_paths() {
+        let root = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(root.path()).unwrap();
+        let inside = root.join("rt").join("lua.wasm");
+        assert_eq!(project_relative(&root, &inside.to_string_lossy()), "rt/lua.wasm");
+        assert_eq!(project_relative(&root, "/opt/lua.wasm"), "/opt/lua.wasm");
+        assert_eq!(Path::new("/checkout").join(project_relative(&root, &inside.to_string_lossy())), Path::new("/checkout/rt/lua.wasm"));
+    }
+}
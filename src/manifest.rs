@@ -0,0 +1,122 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+pub const MANIFEST_FILE: &str = "sdk.toml";
+
+// Describes how to run an SDK. Lives at `<plugin dir>/sdk.toml`; SDKs
+// without one get a built-in manifest matching the historical layout.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default = "default_wasm")]
+    pub wasm: String,
+    #[serde(default = "default_entry")]
+    pub entry: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mounts: Vec<ManifestMount>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestMount {
+    pub host: String,
+    pub guest: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub writable: bool,
+}
+
+fn default_wasm() -> String {
+    "runtime.wasm".to_string()
+}
+
+fn default_entry() -> String {
+    "_start".to_string()
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Manifest {
+            version: None,
+            wasm: default_wasm(),
+            entry: default_entry(),
+            args: Vec::new(),
+            env: BTreeMap::new(),
+            mounts: Vec::new(),
+            extensions: Vec::new(),
+        }
+    }
+}
+
+impl Manifest {
+    pub fn load(dir: &Path) -> Result<Option<Manifest>> {
+        let path = dir.join(MANIFEST_FILE);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(anyhow!("Cannot read manifest '{}': {}", path.display(), e)),
+        };
+        let manifest: Manifest =
+            toml::from_str(&content).map_err(|e| anyhow!("Invalid manifest '{}': {}", path.display(), e))?;
+        for mount in &manifest.mounts {
+            if !is_contained(Path::new(&mount.host)) {
+                return Err(anyhow!(
+                    "Invalid manifest '{}': mount '{}' must be a path inside the SDK directory",
+                    path.display(),
+                    mount.host
+                ));
+            }
+        }
+        if !is_contained(Path::new(&manifest.wasm)) {
+            return Err(anyhow!(
+                "Invalid manifest '{}': wasm '{}' must be a path inside the SDK directory",
+                path.display(),
+                manifest.wasm
+            ));
+        }
+        Ok(Some(manifest))
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        fs::write(dir.join(MANIFEST_FILE), toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn wasm_path(&self, dir: &Path) -> PathBuf {
+        dir.join(&self.wasm)
+    }
+
+    // Expands the argv template. `{script}` is replaced by the guest path of
+    // the script and an element that is exactly `{args}` by the script's
+    // arguments; without `{args}` they are appended at the end.
+    pub fn argv(&self, script: &str, args: &[String]) -> Vec<String> {
+        let mut argv = Vec::new();
+        let mut spliced = false;
+        for item in &self.args {
+            if item == "{args}" {
+                argv.extend(args.iter().cloned());
+                spliced = true;
+            } else {
+                argv.push(item.replace("{script}", script));
+            }
+        }
+        if !spliced {
+            argv.extend(args.iter().cloned());
+        }
+        argv
+    }
+}
+
+fn is_contained(path: &Path) -> bool {
+    path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
@@ -0,0 +1,82 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const ORIGIN_FILE: &str = "origin.toml";
+
+// Records where an installed SDK came from, so `sdk update` can tell whether
+// the source has changed. Written by rchidrun at `<plugin dir>/origin.toml`.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Origin {
+    pub sha256: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    pub installed_at: u64,
+    pub source: Source,
+    // SHA-256 of each installed file, so a locked SDK can be re-checked.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub files: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Source {
+    Wasmer {
+        package: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        constraint: Option<String>,
+        version: String,
+    },
+    Url {
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        etag: Option<String>,
+    },
+    File {
+        path: String,
+    },
+}
+
+impl Origin {
+    pub fn new(source: Source, sha256: String) -> Origin {
+        let installed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Origin {
+            sha256,
+            signature: None,
+            installed_at,
+            source,
+            files: BTreeMap::new(),
+        }
+    }
+
+    pub fn load(dir: &Path) -> Result<Option<Origin>> {
+        let path = dir.join(ORIGIN_FILE);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(anyhow!("Cannot read origin '{}': {}", path.display(), e)),
+        };
+        toml::from_str(&content).map_err(|e| anyhow!("Invalid origin '{}': {}", path.display(), e))
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        fs::write(dir.join(ORIGIN_FILE), toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Source::Wasmer { package, version, .. } => write!(f, "{}@{}", package, version),
+            Source::Url { url, .. } => write!(f, "{}", url),
+            Source::File { path } => write!(f, "{}", path),
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use crate::manifest::{Manifest, ManifestMount};
+use anyhow::{anyhow, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use webc::metadata::annotations::{FileSystemMapping, Wasi};
+use webc::{Container, Volume};
+
+pub const CONTAINER_FILE: &str = "package.webc";
+const VOLUMES_DIR: &str = "fs";
+
+// Finds a Wasmer package container left in an SDK directory, either by our
+// own download or by an older `wasmer install`.
+pub fn find_container(sdk_path: &Path) -> Option<PathBuf> {
+    let preferred = sdk_path.join(CONTAINER_FILE);
+    if preferred.is_file() {
+        return Some(preferred);
+    }
+    fs::read_dir(sdk_path)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| path.extension().is_some_and(|e| e == "webc") && path.is_file())
+}
+
+// Extracts the entrypoint module into `runtime.wasm`, every filesystem volume
+// into `fs/<volume>/`, and writes an `sdk.toml` that preopens them where the
+// package mounts them.
+pub fn unpack(container_path: &Path, sdk_path: &Path, version: Option<String>) -> Result<()> {
+    let container = Container::from_disk(container_path)
+        .map_err(|e| anyhow!("Invalid package '{}': {}", container_path.display(), e))?;
+    let manifest = container.manifest();
+    let command = match &manifest.entrypoint {
+        Some(name) => name.clone(),
+        None if manifest.commands.len() == 1 => manifest.commands.keys().next().cloned().unwrap_or_default(),
+        None => {
+            let names: Vec<&str> = manifest.commands.keys().map(String::as_str).collect();
+            return Err(anyhow!(
+                "Package '{}' has no entrypoint; commands: {}",
+                container_path.display(),
+                names.join(", ")
+            ));
+        }
+    };
+    let command_spec = &manifest.commands[&command];
+    let wasi = command_spec
+        .annotation::<Wasi>(Wasi::KEY)
+        .ok()
+        .flatten()
+        .ok_or(anyhow!("Command '{}' is not a WASI command", command))?;
+    // The `atom` annotation names the module; packages built before it existed
+    // only have it in the `wasi` annotation, which `Command::atom` reads too.
+    let module = command_spec
+        .atom()
+        .ok()
+        .flatten()
+        .ok_or(anyhow!("Command '{}' does not name a module", command))?;
+    if let Some(dependency) = &module.dependency {
+        return Err(anyhow!(
+            "Command '{}' runs module '{}' of dependency '{}', which is not supported",
+            command,
+            module.name,
+            dependency
+        ));
+    }
+    let atom = container
+        .get_atom(&module.name)
+        .ok_or(anyhow!("Module '{}' not found in package", module.name))?;
+    let mut sdk_manifest = Manifest::load(sdk_path)?.unwrap_or_default();
+    fs::write(sdk_path.join(&sdk_manifest.wasm), &atom[..])?;
+
+    let volumes_path = sdk_path.join(VOLUMES_DIR);
+    if volumes_path.exists() {
+        fs::remove_dir_all(&volumes_path)?;
+    }
+    let mappings = manifest
+        .filesystem()
+        .map_err(|e| anyhow!("Invalid package '{}': {}", container_path.display(), e))?;
+    let mounts = match mappings {
+        Some(mappings) => mount_mappings(&container, &mappings, &volumes_path)?,
+        None => mount_volumes(&container, &volumes_path)?,
+    };
+
+    let mut args = vec![command];
+    args.extend(wasi.main_args.unwrap_or_default());
+    args.push("{script}".to_string());
+    sdk_manifest.args = args;
+    sdk_manifest.mounts = mounts;
+    if version.is_some() {
+        sdk_manifest.version = version;
+    }
+    for var in wasi.env.unwrap_or_default() {
+        if let Some((key, value)) = var.split_once('=') {
+            sdk_manifest.env.insert(key.to_string(), value.to_string());
+        }
+    }
+    sdk_manifest.save(sdk_path)
+}
+
+// Extracts each volume named by the package's `fs` mappings into
+// `fs/<volume>/` and mounts it where the package asks for it.
+fn mount_mappings(container: &Container, mappings: &[FileSystemMapping], volumes_path: &Path) -> Result<Vec<ManifestMount>> {
+    let volumes = container.volumes();
+    let mut extracted = HashSet::new();
+    let mut mounts = Vec::new();
+    for mapping in mappings {
+        if let Some(dependency) = &mapping.from {
+            return Err(anyhow!(
+                "Package mounts volume '{}' of dependency '{}', which is not supported",
+                mapping.volume_name,
+                dependency
+            ));
+        }
+        let volume = volumes
+            .get(&mapping.volume_name)
+            .ok_or(anyhow!("Volume '{}' missing from package", mapping.volume_name))?;
+        let dir = relative_path(&mapping.volume_name)?;
+        let dest = volumes_path.join(&dir);
+        if extracted.insert(&mapping.volume_name) {
+            extract(volume, "/", &dest)?;
+        }
+        let mut host = Path::new(VOLUMES_DIR).join(dir);
+        if let Some(inner) = &mapping.host_path {
+            host.push(relative_path(inner)?);
+        }
+        if !mapping.mount_path.starts_with('/') {
+            return Err(anyhow!("Refusing to mount a volume at relative path '{}'", mapping.mount_path));
+        }
+        relative_path(&mapping.mount_path)?;
+        mounts.push(ManifestMount {
+            host: host.to_string_lossy().into_owned(),
+            guest: mapping.mount_path.clone(),
+            writable: false,
+        });
+    }
+    Ok(mounts)
+}
+
+// Containers without `fs` mappings predate them; their volumes are laid out
+// from the guest's root, so each top-level directory is mounted at `/<dir>`.
+fn mount_volumes(container: &Container, volumes_path: &Path) -> Result<Vec<ManifestMount>> {
+    let mut mounts = Vec::new();
+    for (name, volume) in container.volumes() {
+        if name == "metadata" || !is_safe_name(&name) {
+            continue;
+        }
+        let dest = volumes_path.join(&name);
+        extract(&volume, "/", &dest)?;
+        for entry in fs::read_dir(&dest)?.flatten() {
+            if entry.path().is_dir() {
+                let dir = entry.file_name().to_string_lossy().into_owned();
+                mounts.push(ManifestMount {
+                    host: format!("{}/{}/{}", VOLUMES_DIR, name, dir),
+                    guest: format!("/{}", dir),
+                    writable: false,
+                });
+            }
+        }
+    }
+    Ok(mounts)
+}
+
+// Volume names and paths inside a package are absolute (`/lib`); they are
+// used as relative paths under the SDK directory, one checked component at a
+// time.
+fn relative_path(path: &str) -> Result<PathBuf> {
+    let mut relative = PathBuf::new();
+    for part in path.split('/').filter(|p| !p.is_empty()) {
+        if !is_safe_name(part) {
+            return Err(anyhow!("Refusing to extract unsafe path '{}'", path));
+        }
+        relative.push(part);
+    }
+    Ok(relative)
+}
+
+fn extract(volume: &Volume, path: &str, dest: &Path) -> Result<()> {
+    fs::create_dir_all(dest)?;
+    let entries = volume
+        .read_dir(path)
+        .ok_or(anyhow!("Directory '{}' missing from package volume", path))?;
+    for (name, _, metadata) in entries {
+        let name = name.to_string();
+        if !is_safe_name(&name) {
+            return Err(anyhow!("Refusing to extract unsafe path '{}/{}'", path, name));
+        }
+        let child = format!("{}/{}", path.trim_end_matches('/'), name);
+        if metadata.is_dir() {
+            extract(volume, &child, &dest.join(&name))?;
+        } else {
+            let (data, _) = volume
+                .read_file(child.as_str())
+                .ok_or(anyhow!("File '{}' missing from package volume", child))?;
+            fs::write(dest.join(&name), &data[..])?;
+        }
+    }
+    Ok(())
+}
+
+fn is_safe_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webc::wasmer_package::Package;
+
+    const WASM: &[u8] = b"\0asm\x01\0\0\0";
+
+    // Builds a container the way `wasmer publish` does, from a `wasmer.toml`.
+    fn container(dir: &Path, fs_table: &str) -> PathBuf {
+        let source = dir.join("src");
+        fs::create_dir_all(source.join("lib/std")).unwrap();
+        fs::create_dir_all(source.join("share/zz")).unwrap();
+        fs::write(source.join("zz.wasm"), WASM).unwrap();
+        fs::write(source.join("lib/std/os.zz"), "os").unwrap();
+        fs::write(source.join("share/zz/motd"), "hi").unwrap();
+        fs::write(
+            source.join("wasmer.toml"),
+            format!(
+                r#"
+[package]
+name = "test/zz"
+version = "1.2.0"
+description = "zz"
+
+[[module]]
+name = "zz-core"
+source = "zz.wasm"
+abi = "wasi"
+
+[[command]]
+name = "zz"
+module = "zz-core"
+runner = "wasi"
+
+[command.annotations.wasi]
+main-args = ["-q"]
+env = ["ZZHOME=/lib"]
+
+{}
+"#,
+                fs_table
+            ),
+        )
+        .unwrap();
+        let package = Package::from_manifest(source.join("wasmer.toml")).unwrap();
+        let path = dir.join(CONTAINER_FILE);
+        fs::write(&path, package.serialize().unwrap()).unwrap();
+        path
+    }
+
+    #[test]
+    fn unpack_mounts_volumes_where_the_package_declares() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = container(dir.path(), "[fs]\n\"/lib\" = \"lib\"\n\"/usr/share\" = \"share\"\n");
+        let sdk = dir.path().join("sdk");
+        fs::create_dir(&sdk).unwrap();
+        unpack(&path, &sdk, Some("1.2.0".to_string())).unwrap();
+
+        assert_eq!(fs::read(sdk.join("runtime.wasm")).unwrap(), WASM);
+        assert_eq!(fs::read_to_string(sdk.join("fs/lib/std/os.zz")).unwrap(), "os");
+        assert_eq!(fs::read_to_string(sdk.join("fs/share/zz/motd")).unwrap(), "hi");
+        let manifest = Manifest::load(&sdk).unwrap().unwrap();
+        let mounts: Vec<_> = manifest.mounts.iter().map(|m| (m.host.as_str(), m.guest.as_str())).collect();
+        assert_eq!(mounts, [("fs/lib", "/lib"), ("fs/share", "/usr/share")]);
+        assert_eq!(manifest.args, ["zz", "-q", "{script}"]);
+        assert_eq!(manifest.env["ZZHOME"], "/lib");
+        assert_eq!(manifest.version.as_deref(), Some("1.2.0"));
+    }
+
+    #[test]
+    fn unpack_without_volumes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = container(dir.path(), "");
+        let sdk = dir.path().join("sdk");
+        fs::create_dir(&sdk).unwrap();
+        unpack(&path, &sdk, None).unwrap();
+        assert_eq!(fs::read(sdk.join("runtime.wasm")).unwrap(), WASM);
+        assert!(Manifest::load(&sdk).unwrap().unwrap().mounts.is_empty());
+    }
+
+    #[test]
+    fn relative_path_checks_each_component() {
+        assert_eq!(relative_path("/lib").unwrap(), Path::new("lib"));
+        assert_eq!(relative_path("/nested/dir/").unwrap(), Path::new("nested/dir"));
+        assert_eq!(relative_path("/").unwrap(), Path::new(""));
+        for path in ["/../etc", "/lib/../..", "/a/./b", "/a\\b"] {
+            assert!(relative_path(path).is_err(), "{:?}", path);
+        }
+    }
+}
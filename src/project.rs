@@ -0,0 +1,139 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::layout;
+
+pub const PROJECT_FILE: &str = ".rchidrun.toml";
+
+// Project settings shared by everyone running scripts from a repository,
+// found by walking up from the script's directory. Relative paths are
+// resolved against the directory containing the file.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Project {
+    #[serde(skip)]
+    pub root: PathBuf,
+    sdk: BTreeMap<String, toml::Value>,
+    #[serde(skip)]
+    sdks: BTreeMap<String, SdkPin>,
+    pub extensions: HashMap<String, String>,
+    pub run: RunDefaults,
+}
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SdkPin {
+    pub version: Option<String>,
+    pub wasmer: Option<String>,
+    pub url: Option<String>,
+    pub file: Option<PathBuf>,
+    pub sha256: Option<String>,
+    pub signature: Option<String>,
+}
+
+// Defaults for `rchidrun run`, named after the command-line flags they
+// stand in for. Flags given on the command line take precedence.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct RunDefaults {
+    pub dir: Vec<String>,
+    pub dir_ro: Vec<String>,
+    pub cwd: bool,
+    pub env: BTreeMap<String, String>,
+    pub env_file: Vec<PathBuf>,
+    pub inherit_env: Vec<String>,
+    pub timeout: Option<String>,
+    pub max_memory: Option<String>,
+    pub fuel: Option<u64>,
+}
+
+impl Project {
+    pub fn discover(start: &Path) -> Result<Option<Project>> {
+        for dir in start.ancestors() {
+            let path = dir.join(PROJECT_FILE);
+            if path.is_file() {
+                return Project::load(&path).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn load(path: &Path) -> Result<Project> {
+        let content =
+            fs::read_to_string(path).map_err(|e| anyhow!("Cannot read project file '{}': {}", path.display(), e))?;
+        let mut project: Project =
+            toml::from_str(&content).map_err(|e| anyhow!("Invalid project file '{}': {}", path.display(), e))?;
+        project.root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let invalid = |e: anyhow::Error| anyhow!("Invalid project file '{}': {}", path.display(), e);
+        project.extensions = config::normalize_extensions(std::mem::take(&mut project.extensions))
+            .map_err(|e| invalid(anyhow!(e)))?;
+        for language in project.extensions.values() {
+            layout::check_name("language", language).map_err(invalid)?;
+        }
+        for (language, value) in std::mem::take(&mut project.sdk) {
+            layout::check_name("language", &language).map_err(invalid)?;
+            let mut pin = match value {
+                toml::Value::String(version) => SdkPin {
+                    version: Some(version),
+                    ..SdkPin::default()
+                },
+                value => SdkPin::deserialize(value)
+                    .map_err(|e| anyhow!("Invalid [sdk.{}] in '{}': {}", language, path.display(), e))?,
+            };
+            if let Some(version) = &pin.version {
+                layout::check_name("version", version).map_err(invalid)?;
+            }
+            let sources = [pin.wasmer.is_some(), pin.url.is_some(), pin.file.is_some()];
+            if sources.iter().filter(|s| **s).count() > 1 {
+                return Err(anyhow!(
+                    "Invalid [sdk.{}] in '{}': set only one of wasmer, url and file",
+                    language,
+                    path.display()
+                ));
+            }
+            pin.file = pin.file.map(|file| project.path(&file));
+            pin.signature = pin.signature.map(|signature| {
+                if signature.contains("://") {
+                    signature
+                } else {
+                    project.path(Path::new(&signature)).display().to_string()
+                }
+            });
+            project.sdks.insert(language, pin);
+        }
+        Ok(project)
+    }
+
+    // Languages the project refers to, either by pinning them or by mapping
+    // extensions to them.
+    pub fn languages(&self) -> Vec<String> {
+        let mut languages: Vec<String> = self.sdks.keys().chain(self.extensions.values()).cloned().collect();
+        languages.sort();
+        languages.dedup();
+        languages
+    }
+
+    // Renames languages written as aliases (`py`, `node`) to the names their
+    // SDKs are installed under.
+    pub fn canonicalize(&mut self, canonical: impl Fn(&str) -> String) {
+        self.sdks = std::mem::take(&mut self.sdks)
+            .into_iter()
+            .map(|(language, pin)| (canonical(&language), pin))
+            .collect();
+        for language in self.extensions.values_mut() {
+            *language = canonical(language);
+        }
+    }
+
+    pub fn pin(&self, language: &str) -> Option<&SdkPin> {
+        self.sdks.get(language)
+    }
+
+    pub fn path(&self, path: &Path) -> PathBuf {
+        self.root.join(path)
+    }
+}
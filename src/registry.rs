@@ -0,0 +1,140 @@
+use anyhow::{anyhow, Result};
+use reqwest::StatusCode;
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+
+use crate::download::{self, Download, Downloader, Failed};
+
+pub const DEFAULT_REGISTRY: &str = "https://registry.wasmer.io/graphql";
+
+const PACKAGE_QUERY: &str = "query ($name: String!) {
+  getPackage(name: $name) {
+    versions {
+      version
+      distribution { piritaDownloadUrl piritaSha256Hash }
+    }
+  }
+}";
+
+#[derive(Deserialize)]
+struct Response {
+    data: Option<Data>,
+    #[serde(default)]
+    errors: Vec<ResponseError>,
+}
+
+#[derive(Deserialize)]
+struct ResponseError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Data {
+    get_package: Option<Package>,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    versions: Vec<PackageVersion>,
+}
+
+#[derive(Deserialize)]
+struct PackageVersion {
+    version: String,
+    distribution: Distribution,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Distribution {
+    pirita_download_url: Option<String>,
+    pirita_sha256_hash: Option<String>,
+}
+
+pub struct Release {
+    pub name: String,
+    pub version: String,
+    pub url: String,
+    pub sha256: Option<String>,
+}
+
+// Splits `namespace/name[@constraint]` into the package name and a version
+// requirement; no constraint means the newest stable release.
+pub fn parse_spec(spec: &str) -> Result<(String, VersionReq)> {
+    let (name, req) = match spec.split_once('@') {
+        Some((name, req)) => (name, VersionReq::parse(req).map_err(|e| anyhow!("Invalid version '{}': {}", req, e))?),
+        None => (spec, VersionReq::STAR),
+    };
+    if name.split('/').count() != 2 || name.split('/').any(str::is_empty) {
+        return Err(anyhow!("Invalid package name '{}', expected namespace/name", name));
+    }
+    Ok((name.to_string(), req))
+}
+
+// Queries use the download timeouts, and are retried like downloads.
+pub fn resolve(endpoint: &str, spec: &str, downloader: &Downloader) -> Result<Release> {
+    let (name, req) = parse_spec(spec)?;
+    let body = serde_json::json!({ "query": PACKAGE_QUERY, "variables": { "name": name } });
+    let client = downloader.client()?;
+    let text = downloader
+        .retrying(&format!("Registry request to {}", endpoint), || {
+            let resp = client.post(endpoint).json(&body).send().map_err(|e| Failed::Retry(e.into()))?;
+            let status = resp.status();
+            if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+                return Err(Failed::Retry(anyhow!("HTTP {}", status)));
+            }
+            if !status.is_success() {
+                return Err(Failed::Fatal(anyhow!("HTTP {}", status)));
+            }
+            resp.text().map_err(|e| Failed::Retry(e.into()))
+        })
+        .map_err(|e| anyhow!("Registry request to {} failed: {}", endpoint, e))?;
+    let response: Response =
+        serde_json::from_str(&text).map_err(|e| anyhow!("Invalid registry response from {}: {}", endpoint, e))?;
+    if let Some(error) = response.errors.first() {
+        return Err(anyhow!("Registry error: {}", error.message));
+    }
+    let package = response
+        .data
+        .and_then(|d| d.get_package)
+        .ok_or(anyhow!("Package '{}' not found in registry", name))?;
+    package
+        .versions
+        .into_iter()
+        .filter_map(|v| {
+            let parsed = Version::parse(&v.version).ok()?;
+            let url = v.distribution.pirita_download_url?;
+            req.matches(&parsed).then_some((
+                parsed,
+                Release {
+                    name: name.clone(),
+                    version: v.version,
+                    url,
+                    sha256: v.distribution.pirita_sha256_hash,
+                },
+            ))
+        })
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, release)| release)
+        .ok_or(anyhow!("No release of '{}' matches '{}'", name, req))
+}
+
+pub fn download(release: &Release, downloader: &Downloader) -> Result<Download> {
+    let download = downloader
+        .fetch(&release.url, None)?
+        .ok_or(anyhow!("Failed to download {}: unexpected 304 response", release.url))?;
+    if let Some(expected) = &release.sha256 {
+        let actual = download::sha256_file(&download.path)?;
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(anyhow!(
+                "Checksum mismatch for {}@{}: expected {}, got {}",
+                release.name,
+                release.version,
+                expected,
+                actual
+            ));
+        }
+    }
+    Ok(download)
+}
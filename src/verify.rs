@@ -0,0 +1,63 @@
+use anyhow::{anyhow, Result};
+use minisign_verify::{Error, PublicKey, Signature};
+use std::fs;
+use std::path::Path;
+
+// Checks a downloaded runtime must pass before it is written into the plugin
+// directory.
+#[derive(Default, Clone)]
+pub struct Verify {
+    pub sha256: Option<String>,
+    pub signature: Option<String>,
+}
+
+pub fn check_sha256(actual: &str, expected: &str) -> Result<()> {
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(anyhow!("Checksum mismatch: expected {}, got {}", expected, actual));
+    }
+    Ok(())
+}
+
+// Reads minisign public keys, one per line, as printed by `minisign -G`.
+// Blank lines, `#` comments and `untrusted comment:` lines are skipped.
+pub fn load_trusted_keys(path: &Path) -> Result<Vec<PublicKey>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(anyhow!("Cannot read trusted keys '{}': {}", path.display(), e)),
+    };
+    let mut keys = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("untrusted comment:") {
+            continue;
+        }
+        let key = PublicKey::from_base64(line)
+            .map_err(|e| anyhow!("Invalid key on line {} of '{}': {}", number + 1, path.display(), e))?;
+        keys.push(key);
+    }
+    if keys.is_empty() {
+        return Err(anyhow!(
+            "No trusted keys in '{}'; add the publisher's minisign public key to verify signatures",
+            path.display()
+        ));
+    }
+    Ok(keys)
+}
+
+pub fn check_signature(bytes: &[u8], signature: &str, keys: &[PublicKey]) -> Result<()> {
+    let signature = Signature::decode(signature).map_err(|e| anyhow!("Invalid signature: {}", e))?;
+    let mut untrusted = true;
+    for key in keys {
+        match key.verify(bytes, &signature, true) {
+            Ok(()) => return Ok(()),
+            Err(Error::UnexpectedKeyId) => continue,
+            Err(_) => untrusted = false,
+        }
+    }
+    if untrusted {
+        Err(anyhow!("Signature was made with a key that is not trusted"))
+    } else {
+        Err(anyhow!("Signature verification failed"))
+    }
+}